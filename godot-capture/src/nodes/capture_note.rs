@@ -1,82 +1,406 @@
-use crate::nodes::inbox::{GitlabStorage, Inbox, InboxError, Storage};
+use crate::nodes::inbox::validate;
+use crate::nodes::inbox::{
+    format_for, CaptureTemplate, FileStorage, GitRepoStorage, GithubStorage, GitlabStorage, Inbox,
+    InboxError, InboxWorker, Note, NoteError, Outbox, OutboxError, ReminderChange, ReminderRef,
+    Storage, StorageError, SystemClock, TemplatedStorage, WorkerEvent,
+};
 use crate::nodes::oauth::{AuthState, TokenRetriever};
-use gdnative::api::{AcceptDialog, Control, TextEdit, TextureButton};
+use crate::nodes::settings::{Settings, SettingsError, StorageConfig, SETTINGS_FILE};
+use gdnative::api::{
+    AcceptDialog, Control, ItemList, LineEdit, OptionButton, TextEdit, TextureButton,
+    VBoxContainer, OS,
+};
 use gdnative::prelude::*;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+const OUTBOX_FILE: &str = "outbox.json";
+const LOGIN_SCENE: &str = "res://main.tscn";
+
 #[derive(Debug, Error)]
 pub enum CaptureError {
     #[error("Token Not Available")]
     TokenFailure,
 
+    #[error("{0}")]
+    Configuration(#[from] SettingsError),
+
     #[error("Error getting inbox: {0}")]
     ErrorGettingInbox(#[from] InboxError),
+
+    #[error("{0}")]
+    Outbox(#[from] OutboxError),
+}
+
+impl CaptureError {
+    pub fn storage_error(&self) -> Option<&StorageError> {
+        match self {
+            CaptureError::ErrorGettingInbox(err) => err.storage_error(),
+            CaptureError::Outbox(OutboxError::CouldNotDeliver(err)) => err.storage_error(),
+            _ => None,
+        }
+    }
+
+    /// A note that can't be saved, which is shown next to it rather than in a
+    /// dialog.
+    pub fn note_error(&self) -> Option<&NoteError> {
+        match self {
+            CaptureError::ErrorGettingInbox(InboxError::InvalidNote(err)) => Some(err),
+            CaptureError::Outbox(OutboxError::CouldNotDeliver(InboxError::InvalidNote(err))) => {
+                Some(err)
+            }
+            _ => None,
+        }
+    }
+
+    /// True when logging in again would fix the problem, rather than waiting
+    /// for storage to come back.
+    pub fn needs_login(&self) -> bool {
+        matches!(self, CaptureError::TokenFailure)
+            || self.storage_error() == Some(&StorageError::Unauthorized)
+    }
+
+    pub fn is_temporary(&self) -> bool {
+        matches!(
+            self.storage_error(),
+            Some(StorageError::Network(_)) | Some(StorageError::RateLimited)
+        )
+    }
 }
 
 #[derive(NativeClass)]
 #[inherit(Control)]
+#[register_with(Self::register_signals)]
 pub struct Remember {
-    inbox: Option<Inbox<GitlabStorage>>,
+    worker: Option<InboxWorker<CaptureError>>,
+    saving: bool,
+    editing: bool,
+    reminders: Vec<ReminderRef>,
+    select_after_change: Option<usize>,
+    logs_in_with_oauth: bool,
+    templates: Vec<CaptureTemplate>,
 }
 
 #[methods]
 impl Remember {
     fn new(_owner: &Control) -> Self {
-        Remember { inbox: None }
+        Remember {
+            worker: None,
+            saving: false,
+            editing: false,
+            reminders: Vec::new(),
+            select_after_change: None,
+            logs_in_with_oauth: false,
+            templates: Vec::new(),
+        }
+    }
+
+    fn register_signals(builder: &ClassBuilder<Self>) {
+        builder.add_signal(Signal {
+            name: "inbox_loaded",
+            args: &[string_argument("reminders")],
+        });
+        builder.add_signal(Signal {
+            name: "reminder_saved",
+            args: &[string_argument("reminders")],
+        });
+        builder.add_signal(Signal {
+            name: "save_failed",
+            args: &[string_argument("error")],
+        });
+        builder.add_signal(Signal {
+            name: "reminder_changed",
+            args: &[string_argument("reminders")],
+        });
+        builder.add_signal(Signal {
+            name: "change_failed",
+            args: &[string_argument("error")],
+        });
     }
 
     #[export]
     fn _ready(&mut self, owner: TRef<Control>) {
-        self.inbox = create_storage(&AuthState::get())
-            .and_then(|storage| load_inbox(storage))
+        let outbox = open_outbox()
             .or_else(|err| {
                 display_error(owner, &err);
                 Err(err)
             })
             .ok();
+        let settings_path = user_data_path(SETTINGS_FILE);
+        let settings = Settings::load(&settings_path).ok();
+        self.logs_in_with_oauth = settings
+            .as_ref()
+            .map(|settings| matches!(settings.storage, StorageConfig::Gitlab(_)))
+            .unwrap_or(false);
+        self.templates = settings
+            .as_ref()
+            .map(|settings| settings.templates.clone())
+            .unwrap_or_default();
+        show_templates(owner, &self.templates);
+        let batch_window = settings
+            .and_then(|settings| settings.batching)
+            .map(|batching| batching.window());
+
+        let device = OS::godot_singleton().get_name().to_string();
+
+        clear_list(owner);
+        let worker = InboxWorker::start(
+            move || open_inbox(&settings_path, &device),
+            outbox,
+            batch_window,
+        );
+        worker.load();
+        self.worker = Some(worker);
+    }
 
-        match &self.inbox {
-            Some(inbox) => update_view(owner, &inbox.reminders()),
-            None => clear_list(owner),
+    #[export]
+    fn _process(&mut self, owner: TRef<Control>, _delta: f64) {
+        let events = match &self.worker {
+            Some(worker) => worker.events().collect::<Vec<WorkerEvent<CaptureError>>>(),
+            None => return,
+        };
+        for event in events {
+            self.handle_event(owner, event);
         }
     }
 
     #[export]
     fn _save_me(&mut self, owner: TRef<Control>) {
-        let new_reminder = new_reminder_window(owner);
+        let new_reminder = new_reminder_window(owner).text().to_string();
+        let note = match self.selected_template(owner) {
+            Some(template) => Note::from(new_reminder.as_str())
+                .with_template(&template.name, prompt_answers(owner)),
+            None => Note::from(new_reminder.as_str()),
+        };
+        let note = match validate(&note) {
+            Ok(note) => note,
+            Err(err) => return show_note_error(owner, &err.to_string()),
+        };
+        show_note_error(owner, "");
+        if let Some(worker) = &self.worker {
+            self.saving = true;
+            save_button(owner).set_disabled(true);
+            worker.save_note(note);
+        }
+    }
 
-        if let Some(inbox) = &mut self.inbox {
-            match save_new_reminder(inbox, &new_reminder.text().to_string()) {
-                Ok(_) => update_view(owner, &inbox.reminders()),
-                Err(err) => display_error(owner, &err),
-            }
+    // Picking a template shows a box for each of its prompts.
+    #[export]
+    fn _template_selected(&mut self, owner: TRef<Control>, _index: i64) {
+        show_prompts(owner, self.selected_template(owner));
+    }
+
+    // Double tapping a reminder puts it in the text box to be edited.
+    #[export]
+    fn _reminder_activated(&mut self, owner: TRef<Control>, list_index: i64) {
+        let title = recent_list(owner).get_item_text(list_index).to_string();
+        new_reminder_window(owner).set_text(title);
+    }
+
+    #[export]
+    fn _edit_selected(&mut self, owner: TRef<Control>) {
+        let text = new_reminder_window(owner).text().to_string();
+        show_note_error(owner, "");
+        self.editing = true;
+        self.change_selected(owner, ReminderChange::Edit(text));
+    }
+
+    #[export]
+    fn _delete_selected(&mut self, owner: TRef<Control>) {
+        self.change_selected(owner, ReminderChange::Delete);
+    }
+
+    #[export]
+    fn _mark_done(&mut self, owner: TRef<Control>) {
+        self.change_selected(owner, ReminderChange::Done);
+    }
+
+    #[export]
+    fn _archive_selected(&mut self, owner: TRef<Control>) {
+        self.change_selected(owner, ReminderChange::Archive);
+    }
+
+    #[export]
+    fn _move_up(&mut self, owner: TRef<Control>) {
+        self.change_selected(owner, ReminderChange::MoveUp);
+    }
+
+    #[export]
+    fn _move_down(&mut self, owner: TRef<Control>) {
+        self.change_selected(owner, ReminderChange::MoveDown);
+    }
+
+    #[export]
+    fn _retry_pending(&mut self, _owner: TRef<Control>) {
+        if let Some(worker) = &self.worker {
+            worker.retry();
         }
     }
 
     #[export]
     fn _button_down(&self, owner: TRef<Control>) {
-        let button = owner
-            .get_node("VBoxContainer/CenterContainer/Save")
-            .map(|node| unsafe { node.assume_safe() })
-            .and_then(|node| node.cast::<TextureButton>())
-            .expect("Recent Reminders node is missing");
+        let button = save_button(owner);
         button.set_position(pressed_button(button.position()), false);
     }
 
     #[export]
     fn _button_up(&self, owner: TRef<Control>) {
-        let button = owner
-            .get_node("VBoxContainer/CenterContainer/Save")
-            .map(|node| unsafe { node.assume_safe() })
-            .and_then(|node| node.cast::<TextureButton>())
-            .expect("Recent Reminders node is missing");
+        let button = save_button(owner);
         button.set_position(released_button(button.position()), false);
     }
+
+    // Retries report through the same events as the user's own saves, so the
+    // note being typed is only cleared while one of those is in flight.
+    fn handle_event(&mut self, owner: TRef<Control>, event: WorkerEvent<CaptureError>) {
+        match event {
+            WorkerEvent::InboxLoaded { reminders, refs } => {
+                self.reminders = refs;
+                update_list(owner, &reminders);
+                owner.emit_signal("inbox_loaded", &[Variant::from_str(&reminders)]);
+            }
+            WorkerEvent::LoadFailed { error } => {
+                clear_list(owner);
+                if error.is_temporary() {
+                    godot_warn!("Inbox not loaded, notes will wait until it is: {}", error);
+                } else {
+                    self.report(owner, &error);
+                }
+            }
+            WorkerEvent::ReminderQueued { pending } => {
+                self.finish_saving(owner);
+                new_reminder_window(owner).set_text("");
+                clear_answers(owner);
+                update_pending(owner, pending);
+            }
+            WorkerEvent::ReminderSaved {
+                reminders,
+                refs,
+                pending,
+            } => {
+                self.reminders = refs;
+                if self.saving {
+                    self.finish_saving(owner);
+                    update_view(owner, &reminders);
+                } else {
+                    update_list(owner, &reminders);
+                }
+                update_pending(owner, pending);
+                owner.emit_signal("reminder_saved", &[Variant::from_str(&reminders)]);
+            }
+            WorkerEvent::SaveFailed {
+                error,
+                pending,
+                queued,
+            } => {
+                godot_warn!("Reminders are still waiting to be saved: {}", error);
+                if !queued {
+                    self.finish_saving(owner);
+                }
+                if !queued || error.needs_login() {
+                    self.report(owner, &error);
+                }
+                update_pending(owner, pending);
+                owner.emit_signal("save_failed", &[Variant::from_str(error.to_string())]);
+            }
+            WorkerEvent::ReminderChanged { reminders, refs } => {
+                self.reminders = refs;
+                if self.editing {
+                    self.editing = false;
+                    update_view(owner, &reminders);
+                } else {
+                    update_list(owner, &reminders);
+                }
+                if let Some(index) = self.select_after_change.take() {
+                    select_reminder(owner, self.reminders.len(), index);
+                }
+                owner.emit_signal("reminder_changed", &[Variant::from_str(&reminders)]);
+            }
+            WorkerEvent::ChangeFailed { error } => {
+                self.editing = false;
+                self.select_after_change = None;
+                self.report(owner, &error);
+                owner.emit_signal("change_failed", &[Variant::from_str(error.to_string())]);
+            }
+        }
+    }
+
+    // Only the latest reminders are listed, so the selected row is counted
+    // from the end of the inbox. The worker finds the reminder again by its
+    // ID or title, in case the inbox changed since. A moved reminder stays
+    // selected.
+    fn change_selected(&mut self, owner: TRef<Control>, change: ReminderChange) {
+        let worker = match &self.worker {
+            Some(worker) => worker,
+            None => return,
+        };
+        let list = recent_list(owner);
+        let selected = list.get_selected_items();
+        if selected.len() == 0 {
+            return;
+        }
+        let index = reminder_index(
+            self.reminders.len(),
+            list.get_item_count() as usize,
+            selected.get(0) as usize,
+        );
+        let reminder = match self.reminders.get(index) {
+            Some(reminder) => reminder.clone(),
+            None => return,
+        };
+
+        self.select_after_change = match change {
+            ReminderChange::MoveUp => index.checked_sub(1),
+            ReminderChange::MoveDown => Some(index + 1).filter(|i| *i < self.reminders.len()),
+            _ => None,
+        };
+        worker.change(reminder, change);
+    }
+
+    // Only GitLab tokens come from logging in, the other backends take their
+    // credentials from the settings file so there is nothing to log in to.
+    // Problems with the note itself are shown under it.
+    fn report(&self, owner: TRef<Control>, error: &CaptureError) {
+        if let Some(note_error) = error.note_error() {
+            show_note_error(owner, &note_error.to_string());
+        } else if error.needs_login() && self.logs_in_with_oauth {
+            owner
+                .get_tree()
+                .map(|tree| unsafe { tree.assume_safe() })
+                .map(|tree| {
+                    tree.change_scene(LOGIN_SCENE).expect("Should change scene");
+                });
+        } else {
+            display_error(owner, error);
+        }
+    }
+
+    fn selected_template(&self, owner: TRef<Control>) -> Option<&CaptureTemplate> {
+        template_index(template_picker(owner).selected())
+            .and_then(|index| self.templates.get(index))
+    }
+
+    fn finish_saving(&mut self, owner: TRef<Control>) {
+        self.saving = false;
+        save_button(owner).set_disabled(false);
+    }
+}
+
+fn string_argument(name: &str) -> SignalArgument<'_> {
+    SignalArgument {
+        name,
+        default: Variant::from_str(""),
+        export_info: ExportInfo::new(VariantType::GodotString),
+        usage: PropertyUsage::DEFAULT,
+    }
 }
 
 fn display_error(owner: TRef<Control>, err: &CaptureError) {
+    display_message(owner, &err.to_string());
+}
+
+fn display_message(owner: TRef<Control>, message: &str) {
     let dialog = AcceptDialog::new();
-    dialog.set_text(err.to_string());
+    dialog.set_text(message);
     let dialog = unsafe { dialog.assume_shared() };
     owner.add_child(dialog, false);
     let dialog = unsafe { dialog.assume_safe() };
@@ -84,23 +408,150 @@ fn display_error(owner: TRef<Control>, err: &CaptureError) {
 }
 
 fn update_view(owner: TRef<Control>, inbox: &str) {
-    let inbox_view = owner
-        .get_node("VBoxContainer/Recent Todos")
-        .map(|node| unsafe { node.assume_safe() })
-        .and_then(|node| node.cast::<Label>())
-        .expect("Recent Reminders node is missing");
-    inbox_view.set_text(truncate_to_latest_reminders(inbox));
+    update_list(owner, inbox);
     let new_reminder_window = new_reminder_window(owner);
     new_reminder_window.set_text("");
+    clear_answers(owner);
+}
+
+// The first choice is saving without a template, and the choice is only
+// shown when there are templates.
+fn show_templates(owner: TRef<Control>, templates: &[CaptureTemplate]) {
+    let picker = template_picker(owner);
+    picker.clear();
+    picker.add_item("Note", 0);
+    for (index, template) in templates.iter().enumerate() {
+        picker.add_item(template.name.as_str(), index as i64 + 1);
+    }
+    picker.set_visible(!templates.is_empty());
+}
+
+fn template_index(selected: i64) -> Option<usize> {
+    if selected > 0 {
+        Some(selected as usize - 1)
+    } else {
+        None
+    }
+}
+
+fn show_prompts(owner: TRef<Control>, template: Option<&CaptureTemplate>) {
+    let prompts = prompt_box(owner);
+    for index in (0..prompts.get_child_count()).rev() {
+        if let Some(child) = prompts.get_child(index) {
+            prompts.remove_child(child);
+            unsafe { child.assume_safe() }.queue_free();
+        }
+    }
+    for prompt in template.map_or_else(Vec::new, |template| template.prompts()) {
+        let answer = LineEdit::new();
+        answer.set_placeholder(prompt);
+        let answer = unsafe { answer.assume_shared() };
+        prompts.add_child(answer, false);
+    }
+}
+
+fn prompt_answers(owner: TRef<Control>) -> Vec<String> {
+    answer_boxes(owner)
+        .iter()
+        .map(|answer| answer.text().to_string())
+        .collect()
+}
+
+fn clear_answers(owner: TRef<Control>) {
+    for answer in answer_boxes(owner) {
+        answer.set_text("");
+    }
+}
+
+fn answer_boxes<'a>(owner: TRef<'a, Control>) -> Vec<TRef<'a, LineEdit>> {
+    let prompts = prompt_box(owner);
+    (0..prompts.get_child_count())
+        .filter_map(|index| prompts.get_child(index))
+        .map(|child| unsafe { child.assume_safe() })
+        .filter_map(|child| child.cast::<LineEdit>())
+        .collect()
+}
+
+fn update_list(owner: TRef<Control>, inbox: &str) {
+    let inbox_view = recent_list(owner);
+    inbox_view.clear();
+    for reminder in truncate_to_latest_reminders(inbox).lines() {
+        inbox_view.add_item(reminder, Null::null(), true);
+    }
+}
+
+fn select_reminder(owner: TRef<Control>, reminder_count: usize, index: usize) {
+    let inbox_view = recent_list(owner);
+    let shown = inbox_view.get_item_count() as usize;
+    if let Some(list_index) = (index + shown).checked_sub(reminder_count) {
+        inbox_view.select(list_index as i64, true);
+    }
+}
+
+fn reminder_index(reminder_count: usize, shown: usize, list_index: usize) -> usize {
+    reminder_count.saturating_sub(shown) + list_index
+}
+
+fn update_pending(owner: TRef<Control>, pending: usize) {
+    let pending_view = owner
+        .get_node("VBoxContainer/Pending")
+        .map(|node| unsafe { node.assume_safe() })
+        .and_then(|node| node.cast::<Label>())
+        .expect("Pending node is missing");
+    pending_view.set_text(pending_message(pending));
+}
+
+fn show_note_error(owner: TRef<Control>, message: &str) {
+    let note_error = owner
+        .get_node("VBoxContainer/NoteError")
+        .map(|node| unsafe { node.assume_safe() })
+        .and_then(|node| node.cast::<Label>())
+        .expect("NoteError node is missing");
+    note_error.set_text(message);
+}
+
+fn pending_message(pending: usize) -> String {
+    match pending {
+        0 => String::new(),
+        1 => "1 note waiting to be saved".to_string(),
+        _ => format!("{} notes waiting to be saved", pending),
+    }
 }
 
 fn clear_list(owner: TRef<Control>) {
-    let inbox_view = owner
+    recent_list(owner).clear();
+}
+
+fn recent_list(owner: TRef<Control>) -> TRef<ItemList> {
+    owner
         .get_node("VBoxContainer/Recent Todos")
         .map(|node| unsafe { node.assume_safe() })
-        .and_then(|node| node.cast::<Label>())
-        .expect("Recent Reminders node is missing");
-    inbox_view.set_text("");
+        .and_then(|node| node.cast::<ItemList>())
+        .expect("Recent Reminders node is missing")
+}
+
+fn save_button(owner: TRef<Control>) -> TRef<TextureButton> {
+    owner
+        .get_node("VBoxContainer/CenterContainer/Save")
+        .map(|node| unsafe { node.assume_safe() })
+        .and_then(|node| node.cast::<TextureButton>())
+        .expect("Save button is missing")
+}
+
+fn template_picker(owner: TRef<Control>) -> TRef<OptionButton> {
+    owner
+        .get_node("VBoxContainer/Template")
+        .map(|node| unsafe { node.assume_safe() })
+        .and_then(|node| node.cast::<OptionButton>())
+        .expect("Template node is missing")
+}
+
+fn prompt_box(owner: TRef<Control>) -> TRef<VBoxContainer> {
+    owner
+        .get_node("VBoxContainer/Prompts")
+        .map(|node| unsafe { node.assume_safe() })
+        .and_then(|node| node.cast::<VBoxContainer>())
+        .expect("Prompts node is missing")
 }
 
 fn new_reminder_window(owner: TRef<Control>) -> TRef<TextEdit> {
@@ -142,28 +593,78 @@ fn truncate_to_latest_reminders(all_reminders: &str) -> String {
     }
 }
 
-fn create_storage<T>(token_retriever: &T) -> Result<GitlabStorage, CaptureError>
+fn user_data_path(file: &str) -> PathBuf {
+    PathBuf::from(OS::godot_singleton().get_user_data_dir().to_string()).join(file)
+}
+
+// Reminders are marked as captured from the platform unless the settings name
+// the device.
+fn open_inbox(
+    settings_path: &Path,
+    device: &str,
+) -> Result<Inbox<Box<dyn Storage + Send>>, CaptureError> {
+    let settings = Settings::load(settings_path)?;
+    let storage = create_storage(&AuthState::get(), &settings)?;
+    let mut metadata = settings.metadata;
+    metadata.captured_from = metadata.captured_from.or_else(|| Some(device.to_string()));
+
+    Ok(load_inbox(storage)?
+        .with_format(format_for(&settings.storage.file_path()))
+        .with_target(settings.target)
+        .with_metadata(metadata)
+        .with_keywords(settings.todo_keywords)
+        .with_archive(settings.archive)
+        .with_templates(settings.templates))
+}
+
+fn open_outbox() -> Result<Outbox, CaptureError> {
+    Ok(Outbox::open(&user_data_path(OUTBOX_FILE))?)
+}
+
+fn create_storage<T>(
+    token_retriever: &T,
+    settings: &Settings,
+) -> Result<Box<dyn Storage + Send>, CaptureError>
 where
     T: TokenRetriever,
 {
-    let token = token_retriever.token().ok_or(CaptureError::TokenFailure)?;
+    let token = match &settings.storage {
+        StorageConfig::Gitlab(_) => token_retriever.token().ok_or(CaptureError::TokenFailure)?,
+        _ => String::new(),
+    };
+    let template = settings.storage.path_template()?;
+    if template.is_fixed() {
+        return Ok(open_storage(&settings.storage, &token));
+    }
 
-    Ok(GitlabStorage::new(token.to_string()))
+    // A path with the date in it is opened again for each day's file.
+    let config = settings.storage.clone();
+    Ok(Box::new(TemplatedStorage::new(
+        template,
+        Box::new(SystemClock),
+        Box::new(move |path| open_storage(&config.with_file_path(path), &token)),
+    )))
 }
 
-fn load_inbox<T: Storage>(storage: T) -> Result<Inbox<T>, CaptureError> {
-    Inbox::load(storage).map_err(|err| CaptureError::ErrorGettingInbox(err))
+fn open_storage(config: &StorageConfig, token: &str) -> Box<dyn Storage + Send> {
+    match config {
+        StorageConfig::Gitlab(config) => {
+            Box::new(GitlabStorage::new(token.to_string(), config.clone()))
+        }
+        StorageConfig::Github(config) => Box::new(GithubStorage::new(config.clone())),
+        StorageConfig::File(config) => Box::new(FileStorage::new(config.path.clone())),
+        StorageConfig::Git(config) => Box::new(GitRepoStorage::new(config.clone())),
+    }
 }
 
-fn save_new_reminder<T: Storage>(inbox: &mut Inbox<T>, reminder: &str) -> Result<(), CaptureError> {
-    inbox.save(&reminder.to_string())?;
-    Ok(())
+fn load_inbox<T: Storage>(storage: T) -> Result<Inbox<T>, CaptureError> {
+    Inbox::load(storage).map_err(|err| CaptureError::ErrorGettingInbox(err))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::nodes::inbox::storage::tests::{MockError, MockStorage};
+    use crate::nodes::inbox::storage::tests::MockStorage;
     use crate::nodes::oauth::TokenRetriever;
     use std::rc::Rc;
 
@@ -208,6 +709,19 @@ mod tests {
         assert_eq!(expected, truncate_to_latest_reminders(&full_list));
     }
 
+    #[test]
+    fn the_first_template_choice_is_no_template() {
+        assert_eq!(None, template_index(0));
+        assert_eq!(None, template_index(-1));
+        assert_eq!(Some(1), template_index(2));
+    }
+
+    #[test]
+    fn listed_reminders_are_counted_from_the_end_of_the_inbox() {
+        assert_eq!(6, reminder_index(9, 4, 1));
+        assert_eq!(2, reminder_index(3, 3, 2));
+    }
+
     struct StubTokenRetriever {
         token: Option<String>,
     }
@@ -230,50 +744,100 @@ mod tests {
         }
     }
 
+    fn gitlab_settings() -> Settings {
+        Settings::parse(
+            r#"{"storage": {"backend": "gitlab", "project": 1, "file_path": "inbox.org"}}"#,
+        )
+        .expect("test settings should parse")
+    }
+
+    fn github_settings() -> Settings {
+        Settings::parse(
+            r#"{"storage": {
+                "backend": "github",
+                "owner": "me",
+                "repo": "gtd",
+                "file_path": "inbox.org",
+                "token": "personal-access-token"
+            }}"#,
+        )
+        .expect("test settings should parse")
+    }
+
     #[test]
-    fn when_a_token_is_present_create_storage() -> Result<(), Box<dyn std::error::Error>> {
+    fn when_a_token_is_present_create_storage() {
         let token_retriever = StubTokenRetriever::new_with_token("token");
 
-        let storage = create_storage(&token_retriever)?;
+        let storage = create_storage(&token_retriever, &gitlab_settings());
 
-        assert_eq!("token", storage.token);
-        Ok(())
+        assert!(storage.is_ok());
     }
 
     #[test]
-    fn when_a_token_is_not_present() -> Result<(), Box<dyn std::error::Error>> {
+    fn when_a_token_is_not_present() {
         let token_retriever = StubTokenRetriever::new_without_token();
-        let storage = create_storage(&token_retriever);
+        let storage = create_storage(&token_retriever, &gitlab_settings());
 
-        match storage {
-            Err(CaptureError::TokenFailure) => assert!(true, "correct error"),
-            Ok(token) => assert!(false, println!("unexpected Ok result - {:?}", token)),
-            Err(err) => assert!(false, println!("unexpected error thrown {:?}", err)),
-        }
+        assert!(matches!(storage, Err(CaptureError::TokenFailure)));
+    }
+
+    #[test]
+    fn github_storage_uses_its_own_token() {
+        let token_retriever = StubTokenRetriever::new_without_token();
+
+        let storage = create_storage(&token_retriever, &github_settings());
+
+        assert!(storage.is_ok());
+    }
 
-        Ok(())
+    #[test]
+    fn file_storage_does_not_need_a_token() {
+        let token_retriever = StubTokenRetriever::new_without_token();
+        let settings =
+            Settings::parse(r#"{"storage": {"backend": "file", "path": "/tmp/inbox.org"}}"#)
+                .expect("test settings should parse");
+
+        let storage = create_storage(&token_retriever, &settings);
+
+        assert!(storage.is_ok());
+    }
+
+    #[test]
+    fn a_templated_path_creates_storage() {
+        let token_retriever = StubTokenRetriever::new_with_token("token");
+        let settings = Settings::parse(
+            r#"{"storage": {
+                "backend": "gitlab",
+                "project": 1,
+                "file_path": "journal/{yyyy}/{yyyy}-{mm}-{dd}.org"
+            }}"#,
+        )
+        .expect("test settings should parse");
+
+        let storage = create_storage(&token_retriever, &settings);
+
+        assert!(storage.is_ok());
     }
 
     #[test]
     fn load_todos_from_storage() {
-        let storage = Rc::new(MockStorage::new().with_inbox("-first\nsecond"));
+        let storage = Rc::new(MockStorage::new().with_inbox("* Inbox\n** first\n** second"));
 
         let todos = load_inbox(storage);
         assert!(todos.is_ok());
-        assert_eq!("-first\nsecond", todos.unwrap().reminders());
+        assert_eq!("first\nsecond", todos.unwrap().reminders());
     }
 
     #[test]
     fn map_load_todos_failure_to_capture_error() {
-        let storage = Rc::new(MockStorage::new().with_load_error(MockError::TestFailedToLoad));
+        let storage = Rc::new(MockStorage::new().with_load_error(StorageError::Unauthorized));
 
         let todos = load_inbox(storage);
         match todos {
             Err(CaptureError::ErrorGettingInbox(err)) => match err {
-                InboxError::FailedToLoad(sub_err) => match sub_err.downcast::<MockError>() {
-                    Ok(MockError::TestFailedToLoad) => assert!(true, "correct error"),
-                    _ => assert!(false, "incorrect error"),
-                },
+                InboxError::FailedToLoad(StorageError::Unauthorized) => {
+                    assert!(true, "correct error")
+                }
                 _ => assert!(false, "incorrect error"),
             },
             Ok(_) => assert!(false, println!("unexpected Ok result")),
@@ -282,14 +846,51 @@ mod tests {
     }
 
     #[test]
-    fn save_new_todo_saves() -> Result<(), Box<dyn std::error::Error>> {
-        let storage = Rc::new(MockStorage::new().with_inbox("* Inbox\n** one"));
-        let mut todos = Inbox::load(storage)?;
+    fn an_unauthorized_inbox_needs_a_new_login() {
+        let error =
+            CaptureError::ErrorGettingInbox(InboxError::FailedToLoad(StorageError::Unauthorized));
+
+        assert!(error.needs_login());
+        assert!(!error.is_temporary());
+    }
 
-        save_new_reminder(&mut todos, "two")?;
+    #[test]
+    fn invalid_notes_are_note_errors() {
+        let error = CaptureError::ErrorGettingInbox(InboxError::InvalidNote(NoteError::Empty));
 
-        assert_eq!("one\ntwo", todos.reminders());
-        Ok(())
+        assert_eq!(Some(&NoteError::Empty), error.note_error());
+        assert!(!error.needs_login());
+        assert_eq!(None, CaptureError::TokenFailure.note_error());
+    }
+
+    #[test]
+    fn a_missing_token_needs_a_new_login() {
+        assert!(CaptureError::TokenFailure.needs_login());
+    }
+
+    #[test]
+    fn undelivered_reminders_keep_the_storage_error() {
+        let error = CaptureError::Outbox(OutboxError::CouldNotDeliver(
+            InboxError::CouldNotSaveReminder(StorageError::Network("offline".to_string())),
+        ));
+
+        assert_eq!(
+            Some(&StorageError::Network("offline".to_string())),
+            error.storage_error()
+        );
+        assert!(error.is_temporary());
+        assert!(!error.needs_login());
+    }
+
+    #[test]
+    fn nothing_pending_shows_no_message() {
+        assert_eq!("", pending_message(0));
+    }
+
+    #[test]
+    fn pending_message_counts_the_notes_waiting() {
+        assert_eq!("1 note waiting to be saved", pending_message(1));
+        assert_eq!("3 notes waiting to be saved", pending_message(3));
     }
 
     #[test]
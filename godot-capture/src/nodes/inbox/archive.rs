@@ -0,0 +1,139 @@
+use super::format::InboxFormat;
+use super::org::{Headline, OrgDocument};
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::slice;
+
+/// Where archived reminders go, written like org-archive-location as a file
+/// and a heading separated by `::`. The file is next to the inbox, with `%s`
+/// standing for the inbox's file name, and no file means the inbox itself.
+/// Reminders go under the top level heading, or at the top level of the file
+/// when there is no heading.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ArchiveLocation {
+    file: Option<String>,
+    heading: Option<String>,
+}
+
+impl ArchiveLocation {
+    pub fn new(location: &str) -> Result<Self, String> {
+        let (file, heading) = match location.find("::") {
+            Some(separator) => (&location[..separator], &location[separator + 2..]),
+            None => {
+                return Err(format!(
+                    "the archive location {} needs a :: between the file and the heading",
+                    location
+                ))
+            }
+        };
+        let file = Some(file.trim()).filter(|file| !file.is_empty());
+        let heading =
+            Some(heading.trim().trim_start_matches('*').trim()).filter(|title| !title.is_empty());
+        if file.is_none() && heading.is_none() {
+            return Err(
+                "archiving into the inbox needs a heading to put reminders under".to_string(),
+            );
+        }
+
+        Ok(ArchiveLocation {
+            file: file.map(String::from),
+            heading: heading.map(String::from),
+        })
+    }
+
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// Adds an archived reminder at the end of the archive heading, one level
+    /// below it, adding the heading if the file doesn't have it yet.
+    pub fn file_into(
+        &self,
+        document: &mut OrgDocument,
+        mut reminder: Headline,
+        format: &dyn InboxFormat,
+    ) {
+        match &self.heading {
+            Some(heading) => {
+                let path = if format.has_headings() {
+                    slice::from_ref(heading)
+                } else {
+                    &[]
+                };
+                let parent = document.outline_mut(path, format);
+                format.set_level(&mut reminder, parent.level() + 1);
+                parent.push_child(reminder);
+            }
+            None => {
+                format.set_level(&mut reminder, 1);
+                document.push_headline(reminder);
+            }
+        }
+    }
+}
+
+impl Default for ArchiveLocation {
+    fn default() -> Self {
+        ArchiveLocation {
+            file: Some("%s_archive".to_string()),
+            heading: None,
+        }
+    }
+}
+
+impl TryFrom<String> for ArchiveLocation {
+    type Error = String;
+
+    fn try_from(location: String) -> Result<Self, Self::Error> {
+        ArchiveLocation::new(&location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::format::OrgFormat;
+
+    #[test]
+    fn reads_org_archive_locations() {
+        let location = ArchiveLocation::new("archive/%s::* Archived Tasks").unwrap();
+
+        assert_eq!(Some("archive/%s"), location.file());
+        assert_eq!(Some("Archived Tasks".to_string()), location.heading);
+        assert_eq!(
+            ArchiveLocation::default(),
+            ArchiveLocation::new("%s_archive::").unwrap()
+        );
+        assert_eq!(None, ArchiveLocation::new("::* Archive").unwrap().file());
+    }
+
+    #[test]
+    fn a_location_needs_somewhere_to_put_reminders() {
+        assert!(ArchiveLocation::new("archive.org").is_err());
+        assert!(ArchiveLocation::new("::").is_err());
+        assert!(ArchiveLocation::new(" :: * ").is_err());
+    }
+
+    #[test]
+    fn reminders_go_under_the_heading_or_at_the_top_level() {
+        let reminder = OrgDocument::parse("* Inbox\n** Call mom\n*** details\n").headlines()[0]
+            .children()[0]
+            .clone();
+        let mut archive = OrgDocument::parse("* Archive\n** older\n");
+
+        ArchiveLocation::new("::Archive").unwrap().file_into(
+            &mut archive,
+            reminder.clone(),
+            &OrgFormat,
+        );
+        assert_eq!(
+            "* Archive\n** older\n** Call mom\n*** details\n",
+            archive.to_string()
+        );
+
+        let mut archive = OrgDocument::parse("");
+        ArchiveLocation::default().file_into(&mut archive, reminder, &OrgFormat);
+        assert_eq!("* Call mom\n** details", archive.to_string());
+    }
+}
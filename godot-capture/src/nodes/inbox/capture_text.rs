@@ -0,0 +1,304 @@
+use super::org::Headline;
+use serde::Deserialize;
+
+// Where org puts the end of the tags by default, see org-tags-column.
+const TAGS_COLUMN: usize = 77;
+
+/// The TODO keywords a note may start with, written like org-todo-keywords
+/// with a `|` between the open and the done states.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct TodoKeywords(Vec<String>);
+
+impl TodoKeywords {
+    pub fn new(keywords: &[&str]) -> Self {
+        TodoKeywords(keywords.iter().map(|keyword| keyword.to_string()).collect())
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        word != "|" && self.0.iter().any(|keyword| keyword == word)
+    }
+
+    /// The state new tasks start in.
+    pub fn todo(&self) -> &str {
+        self.0
+            .first()
+            .filter(|keyword| *keyword != "|")
+            .map_or("TODO", |keyword| keyword.as_str())
+    }
+
+    /// The state a reminder gets when it is marked done.
+    pub fn done(&self) -> &str {
+        self.done_states()
+            .first()
+            .map_or("DONE", |keyword| keyword.as_str())
+    }
+
+    pub fn is_done(&self, word: &str) -> bool {
+        self.done_states().iter().any(|keyword| keyword == word)
+    }
+
+    /// The keyword a headline's title starts with, if any.
+    pub fn keyword_in<'a>(&self, title: &'a str) -> Option<&'a str> {
+        title
+            .split_whitespace()
+            .next()
+            .filter(|word| self.contains(word))
+    }
+
+    /// The title with its keyword replaced by `keyword`, or `keyword` put in
+    /// front when it has none, and its tags lined up again.
+    pub fn with_keyword(&self, title: &str, keyword: &str, level: usize) -> String {
+        let title = title.trim_start();
+        let rest = match self.keyword_in(title) {
+            Some(old) => title[old.len()..].trim_start(),
+            None => title,
+        };
+        let text = format!("{} {}", keyword, rest);
+        let text = text.trim_end();
+        match text.rsplitn(2, char::is_whitespace).collect::<Vec<&str>>()[..] {
+            [tags, text] if tag_group(tags).is_some() => align_tags(text.trim_end(), tags, level),
+            _ => text.to_string(),
+        }
+    }
+
+    // Like org, everything after the `|` is done, or the last keyword when
+    // there is no `|`.
+    fn done_states(&self) -> &[String] {
+        match self.0.iter().position(|keyword| keyword == "|") {
+            Some(bar) => &self.0[bar + 1..],
+            None => &self.0[self.0.len().saturating_sub(1)..],
+        }
+    }
+}
+
+impl Default for TodoKeywords {
+    fn default() -> Self {
+        TodoKeywords::new(&["TODO", "|", "DONE"])
+    }
+}
+
+/// The first line of a note picked apart into what goes in an org headline.
+/// `TODO Call dentist :health:phone: [#A]` and `TODO Call dentist #health
+/// #phone [#A]` are the same headline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureText {
+    pub keyword: Option<String>,
+    pub priority: Option<char>,
+    pub title: String,
+    pub tags: Vec<String>,
+}
+
+impl CaptureText {
+    pub fn parse(line: &str, keywords: &TodoKeywords) -> Self {
+        let mut words = line.split_whitespace().collect::<Vec<&str>>();
+        let keyword = match words.first() {
+            Some(word) if keywords.contains(word) => Some(words.remove(0).to_string()),
+            _ => None,
+        };
+
+        let mut priority = None;
+        let mut tags = Vec::new();
+        let mut title = Vec::new();
+        for word in words {
+            if let Some(cookie) = priority_cookie(word).filter(|_| priority.is_none()) {
+                priority = Some(cookie);
+            } else if let Some(word_tags) = tag_group(word) {
+                add_tags(&mut tags, word_tags);
+            } else if let Some(hashtag) = hashtag(word) {
+                add_tags(&mut tags, vec![hashtag]);
+            } else {
+                title.push(word);
+            }
+        }
+
+        CaptureText {
+            keyword,
+            priority,
+            title: title.join(" "),
+            tags,
+        }
+    }
+
+    /// The headline with its tags lined up on the right.
+    pub fn headline(&self, level: usize) -> Headline {
+        let mut text = Vec::new();
+        if let Some(keyword) = &self.keyword {
+            text.push(keyword.clone());
+        }
+        if let Some(priority) = self.priority {
+            text.push(format!("[#{}]", priority));
+        }
+        if !self.title.is_empty() {
+            text.push(self.title.clone());
+        }
+        let mut text = text.join(" ");
+
+        if !self.tags.is_empty() {
+            text = align_tags(&text, &format!(":{}:", self.tags.join(":")), level);
+        }
+        Headline::new(level, &text)
+    }
+}
+
+fn align_tags(text: &str, tags: &str, level: usize) -> String {
+    let used = level + 1 + text.chars().count() + tags.chars().count();
+    let padding = TAGS_COLUMN.saturating_sub(used).max(1);
+    format!("{}{}{}", text, " ".repeat(padding), tags)
+}
+
+fn priority_cookie(word: &str) -> Option<char> {
+    let mut letters = word.strip_prefix("[#")?.strip_suffix(']')?.chars();
+    match (letters.next(), letters.next()) {
+        (Some(letter), None) if letter.is_ascii_uppercase() => Some(letter),
+        _ => None,
+    }
+}
+
+// Tags written the org way, `:one:two:`.
+fn tag_group(word: &str) -> Option<Vec<&str>> {
+    let tags = word.strip_prefix(':')?.strip_suffix(':')?;
+    let tags = tags.split(':').collect::<Vec<&str>>();
+    if tags.iter().all(|tag| is_tag(tag)) {
+        Some(tags)
+    } else {
+        None
+    }
+}
+
+// `#3` or `#2` is a number, not a tag
+fn hashtag(word: &str) -> Option<&str> {
+    word.strip_prefix('#')
+        .filter(|tag| is_tag(tag) && tag.chars().any(char::is_alphabetic))
+}
+
+fn is_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || "_@#%".contains(c))
+}
+
+fn add_tags(tags: &mut Vec<String>, new_tags: Vec<&str>) {
+    for tag in new_tags {
+        if !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> CaptureText {
+        CaptureText::parse(line, &TodoKeywords::default())
+    }
+
+    #[test]
+    fn plain_text_is_just_a_title() {
+        let text = parse("Call the dentist");
+
+        assert_eq!(
+            CaptureText {
+                keyword: None,
+                priority: None,
+                title: "Call the dentist".to_string(),
+                tags: Vec::new(),
+            },
+            text
+        );
+    }
+
+    #[test]
+    fn finds_org_tags_and_the_priority_cookie() {
+        let text = parse("Call dentist :health:phone: [#A]");
+
+        assert_eq!(Some('A'), text.priority);
+        assert_eq!("Call dentist", text.title);
+        assert_eq!(vec!["health", "phone"], text.tags);
+    }
+
+    #[test]
+    fn finds_the_keyword_and_hashtags() {
+        let text = parse("TODO buy milk #errands");
+
+        assert_eq!(Some("TODO".to_string()), text.keyword);
+        assert_eq!("buy milk", text.title);
+        assert_eq!(vec!["errands"], text.tags);
+    }
+
+    #[test]
+    fn only_a_leading_configured_keyword_counts() {
+        let keywords = TodoKeywords::new(&["NEXT", "WAITING", "|", "DONE"]);
+
+        assert_eq!(
+            Some("WAITING".to_string()),
+            CaptureText::parse("WAITING on Bob", &keywords).keyword
+        );
+        assert_eq!(None, CaptureText::parse("TODO later", &keywords).keyword);
+        assert_eq!(None, CaptureText::parse("buy NEXT", &keywords).keyword);
+        assert_eq!(None, CaptureText::parse("| pipe", &keywords).keyword);
+    }
+
+    #[test]
+    fn things_that_only_look_like_tags_stay_in_the_title() {
+        let text = parse("Read chapter #3 at 10:30 in C# [#AB] :: #");
+
+        assert_eq!("Read chapter #3 at 10:30 in C# [#AB] :: #", text.title);
+        assert!(text.tags.is_empty());
+
+        let text = parse("Buy #2 pencils #b2b");
+        assert_eq!("Buy #2 pencils", text.title);
+        assert_eq!(vec!["b2b"], text.tags);
+    }
+
+    #[test]
+    fn tags_are_right_aligned_in_the_headline() {
+        let headline = parse("TODO Call dentist :health:phone: [#A]").headline(2);
+        let line = format!("** {}", headline.title());
+
+        assert_eq!(77, line.chars().count());
+        assert!(line.starts_with("** TODO [#A] Call dentist   "));
+        assert!(line.ends_with(" :health:phone:"));
+    }
+
+    #[test]
+    fn long_titles_leave_a_space_before_the_tags() {
+        let title = "word ".repeat(20);
+        let headline = parse(&format!("{}#tag", title)).headline(2);
+
+        assert!(headline.title().ends_with("word :tag:"));
+    }
+
+    #[test]
+    fn the_done_state_comes_after_the_bar() {
+        let keywords = TodoKeywords::new(&["NEXT", "|", "FINISHED", "CANCELLED"]);
+
+        assert_eq!("FINISHED", keywords.done());
+        assert!(keywords.is_done("CANCELLED"));
+        assert!(!keywords.is_done("NEXT"));
+        assert_eq!("DONE", TodoKeywords::default().done());
+        assert_eq!("DONE", TodoKeywords::new(&["TODO", "DONE"]).done());
+        assert_eq!("DONE", TodoKeywords::new(&[]).done());
+    }
+
+    #[test]
+    fn changing_the_keyword_lines_the_tags_up_again() {
+        let keywords = TodoKeywords::default();
+        let headline = parse("TODO Call dentist #health").headline(2);
+
+        let done = keywords.with_keyword(headline.title(), "DONE", 2);
+
+        assert_eq!(Some("TODO"), keywords.keyword_in(headline.title()));
+        assert_eq!(headline.title().replace("TODO", "DONE"), done);
+        assert_eq!(
+            "DONE Buy milk                                                    :errands:",
+            keywords.with_keyword("Buy milk :errands:", "DONE", 2)
+        );
+        assert_eq!(
+            "DONE Buy milk",
+            keywords.with_keyword("Buy milk", "DONE", 2)
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanningKind {
+    Scheduled,
+    Deadline,
+}
+
+/// A `SCHEDULED:` or `DEADLINE:` line for a headline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Planning {
+    pub kind: PlanningKind,
+    pub date: NaiveDate,
+    pub time: Option<NaiveTime>,
+}
+
+impl fmt::Display for Planning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            PlanningKind::Scheduled => "SCHEDULED:",
+            PlanningKind::Deadline => "DEADLINE:",
+        };
+        write!(f, "{} <{}", kind, self.date.format("%Y-%m-%d %a"))?;
+        if let Some(time) = self.time {
+            write!(f, " {}", time.format("%H:%M"))?;
+        }
+        write!(f, ">")
+    }
+}
+
+/// Takes a date off the end of a title, like `Renew passport due next friday`
+/// or `Pay rent tomorrow 9am`. A date after `due` or `deadline` is a
+/// deadline, anything else is scheduled. The title is left alone when there
+/// is no date at the end, or only a time, or nothing but the date.
+pub fn extract_planning(title: &str, now: NaiveDateTime) -> (String, Option<Planning>) {
+    let words = title.split_whitespace().collect::<Vec<&str>>();
+    let lowercase = words
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>();
+    let mut end = lowercase.len();
+
+    let time = match end
+        .checked_sub(1)
+        .and_then(|last| parse_time(&lowercase[last]))
+    {
+        Some(time) => {
+            end -= 1;
+            if end > 0 && lowercase[end - 1] == "at" {
+                end -= 1;
+            }
+            Some(time)
+        }
+        None => None,
+    };
+
+    let (date, date_words) = match parse_date(&lowercase[..end], now.date()) {
+        Some(date) => date,
+        None => return (title.to_string(), None),
+    };
+    end -= date_words;
+
+    let kind = match end.checked_sub(1).map(|last| lowercase[last].as_str()) {
+        Some("due") | Some("deadline") => {
+            end -= 1;
+            PlanningKind::Deadline
+        }
+        Some("on") | Some("scheduled") => {
+            end -= 1;
+            PlanningKind::Scheduled
+        }
+        _ => PlanningKind::Scheduled,
+    };
+
+    if end == 0 {
+        return (title.to_string(), None);
+    }
+    (words[..end].join(" "), Some(Planning { kind, date, time }))
+}
+
+// The date at the end of the words and how many words it took.
+fn parse_date(words: &[String], today: NaiveDate) -> Option<(NaiveDate, usize)> {
+    let last = words.last()?;
+    match last.as_str() {
+        "today" => return Some((today, 1)),
+        "tomorrow" => return Some((today + Duration::days(1), 1)),
+        _ => {}
+    }
+
+    // "The cat sat" or "Ask about monday" aren't dates, so a weekday has to
+    // come after a word saying it is one.
+    if let Some(weekday) = parse_weekday(last) {
+        let used = match words.len().checked_sub(2).map(|i| words[i].as_str()) {
+            Some("next") => 2,
+            Some("on") | Some("due") | Some("deadline") | Some("scheduled") => 1,
+            _ => return None,
+        };
+        return Some((next_weekday(today, weekday), used));
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(last, "%Y-%m-%d") {
+        return Some((date, 1));
+    }
+
+    // in 3 days, in 2 weeks
+    if words.len() >= 3 && words[words.len() - 3] == "in" {
+        let count = i64::from(words[words.len() - 2].parse::<u32>().ok()?);
+        let days = match last.as_str() {
+            "day" | "days" => count,
+            "week" | "weeks" => count * 7,
+            _ => return None,
+        };
+        return today
+            .checked_add_signed(Duration::days(days))
+            .map(|date| (date, 3));
+    }
+
+    None
+}
+
+// A weekday always means the next one, never today.
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+    let ahead = if ahead == 0 { 7 } else { ahead };
+    today + Duration::days(i64::from(ahead))
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" | "tues" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" | "thurs" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+// 9am, 9:30pm or 14:00. A bare number isn't a time.
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    let (clock, afternoon) = if let Some(clock) = word.strip_suffix("am") {
+        (clock, Some(false))
+    } else if let Some(clock) = word.strip_suffix("pm") {
+        (clock, Some(true))
+    } else {
+        (word, None)
+    };
+
+    let mut parts = clock.splitn(2, ':');
+    let hour = parts.next()?.parse::<u32>().ok()?;
+    let minute = match parts.next() {
+        Some(minute) if minute.len() == 2 => minute.parse::<u32>().ok()?,
+        Some(_) => return None,
+        None if afternoon.is_some() => 0,
+        None => return None,
+    };
+
+    let hour = match afternoon {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some(false) => hour % 12,
+        Some(true) => hour % 12 + 12,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::metadata::tests::capture_time;
+
+    // capture_time is Sunday 2026-10-18 09:12
+    fn extract(title: &str) -> (String, Option<String>) {
+        let (title, planning) = extract_planning(title, capture_time());
+        (title, planning.map(|planning| planning.to_string()))
+    }
+
+    fn planned(title: &str, planning: &str) -> (String, Option<String>) {
+        (title.to_string(), Some(planning.to_string()))
+    }
+
+    #[test]
+    fn due_dates_are_deadlines() {
+        assert_eq!(
+            planned("Renew passport", "DEADLINE: <2026-10-23 Fri>"),
+            extract("Renew passport due next friday")
+        );
+    }
+
+    #[test]
+    fn other_dates_are_scheduled() {
+        assert_eq!(
+            planned("Pay rent", "SCHEDULED: <2026-10-19 Mon 09:00>"),
+            extract("Pay rent tomorrow 9am")
+        );
+        assert_eq!(
+            planned("Call mom", "SCHEDULED: <2026-10-18 Sun 19:30>"),
+            extract("Call mom today at 7:30pm")
+        );
+        assert_eq!(
+            planned("Dentist", "SCHEDULED: <2026-10-21 Wed 14:00>"),
+            extract("Dentist on Wed 14:00")
+        );
+    }
+
+    #[test]
+    fn understands_iso_dates_and_counted_days() {
+        assert_eq!(
+            planned("Taxes", "DEADLINE: <2027-04-15 Thu>"),
+            extract("Taxes deadline 2027-04-15")
+        );
+        assert_eq!(
+            planned("Check the plants", "SCHEDULED: <2026-10-21 Wed>"),
+            extract("Check the plants in 3 days")
+        );
+        assert_eq!(
+            planned("Follow up", "SCHEDULED: <2026-11-01 Sun>"),
+            extract("Follow up in 2 weeks")
+        );
+    }
+
+    #[test]
+    fn a_weekday_is_never_today() {
+        assert_eq!(
+            planned("Church", "SCHEDULED: <2026-10-25 Sun>"),
+            extract("Church on sunday")
+        );
+    }
+
+    #[test]
+    fn leaves_anything_unclear_alone() {
+        for title in &[
+            "Buy milk",
+            "Meet at 9am",
+            "Call back on 10/11",
+            "Taxes due 2026-02-30",
+            "tomorrow",
+            "due friday",
+            "Room 9",
+            "in 3 lessons",
+            "Someday in 4000000000 weeks",
+            "The cat sat",
+            "Lay in the sun",
+            "Ask about monday",
+        ] {
+            assert_eq!((title.to_string(), None), extract(title), "{}", title);
+        }
+    }
+
+    #[test]
+    fn reads_twelve_hour_times() {
+        assert_eq!(NaiveTime::from_hms_opt(0, 0, 0), parse_time("12am"));
+        assert_eq!(NaiveTime::from_hms_opt(12, 15, 0), parse_time("12:15pm"));
+        assert_eq!(None, parse_time("9:5am"));
+        assert_eq!(None, parse_time("25:00"));
+    }
+}
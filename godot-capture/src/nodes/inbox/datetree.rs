@@ -0,0 +1,120 @@
+use super::format::InboxFormat;
+use super::org::Headline;
+use chrono::NaiveDate;
+
+/// The year, month and day headings for a date, like org-capture's
+/// `file+olp+datetree` writes them.
+pub fn date_titles(date: NaiveDate) -> [String; 3] {
+    [
+        date.format("%Y").to_string(),
+        date.format("%Y-%m %B").to_string(),
+        date.format("%Y-%m-%d %A").to_string(),
+    ]
+}
+
+/// The day's heading in the datetree under `parent`.
+pub fn day(parent: &Headline, date: NaiveDate) -> Option<&Headline> {
+    let mut headline = parent;
+    for title in date_titles(date).iter() {
+        headline = headline
+            .children()
+            .iter()
+            .find(|child| date_of(child.title()) == date_of(title))?;
+    }
+    Some(headline)
+}
+
+/// Like `day`, but adds the year, month or day heading when it is missing,
+/// before the first later date so the tree stays in order.
+pub fn day_mut<'a>(
+    parent: &'a mut Headline,
+    date: NaiveDate,
+    format: &dyn InboxFormat,
+) -> &'a mut Headline {
+    let mut headline = parent;
+    for title in date_titles(date).iter() {
+        headline = find_or_insert(headline, title, format);
+    }
+    headline
+}
+
+// Headings are matched on the date at the start of the title, so one written
+// with another weekday name or with tags is still found.
+fn date_of(title: &str) -> &str {
+    title.split_whitespace().next().unwrap_or("")
+}
+
+fn find_or_insert<'a>(
+    parent: &'a mut Headline,
+    title: &str,
+    format: &dyn InboxFormat,
+) -> &'a mut Headline {
+    let date = date_of(title);
+    let position = match parent
+        .children()
+        .iter()
+        .position(|child| date_of(child.title()) == date)
+    {
+        Some(position) => position,
+        None => {
+            let position = parent
+                .children()
+                .iter()
+                .position(|child| is_date(child.title()) && date_of(child.title()) > date)
+                .unwrap_or_else(|| parent.children().len());
+            let heading = format.heading(parent.level() + 1, title);
+            parent.insert_child(position, heading);
+            position
+        }
+    };
+    &mut parent.children_mut()[position]
+}
+
+fn is_date(title: &str) -> bool {
+    title.starts_with(|c: char| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::format::OrgFormat;
+    use crate::nodes::inbox::org::OrgDocument;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).expect("test date should be valid")
+    }
+
+    #[test]
+    fn titles_are_the_year_month_and_day() {
+        assert_eq!(
+            ["2026", "2026-10 October", "2026-10-18 Sunday"],
+            date_titles(date(2026, 10, 18))
+        );
+    }
+
+    #[test]
+    fn missing_headings_go_in_date_order() {
+        let mut document =
+            OrgDocument::parse("* Journal\n** 2025\n** 2027\n*** 2027-01 January\n** Someday\n");
+        let journal = document.outline_mut(&["Journal".to_string()], &OrgFormat);
+
+        day_mut(journal, date(2026, 10, 18), &OrgFormat).push_child(Headline::new(5, "one"));
+        day_mut(journal, date(2027, 1, 2), &OrgFormat);
+        day_mut(journal, date(2027, 1, 1), &OrgFormat);
+
+        assert_eq!(
+            "* Journal\n** 2025\n** 2026\n*** 2026-10 October\n**** 2026-10-18 Sunday\n***** one\n** 2027\n*** 2027-01 January\n**** 2027-01-01 Friday\n**** 2027-01-02 Saturday\n** Someday\n",
+            document.to_string()
+        );
+    }
+
+    #[test]
+    fn days_are_found_by_their_date() {
+        let document = OrgDocument::parse("* 2026\n** 2026-10 Oktober\n*** 2026-10-18 So :home:\n");
+
+        let today = day(document.outline(&[]).unwrap(), date(2026, 10, 18));
+
+        assert_eq!(Some(3), today.map(|day| day.level()));
+        assert!(day(document.outline(&[]).unwrap(), date(2026, 10, 19)).is_none());
+    }
+}
@@ -20,13 +20,15 @@ pub enum DecoderError {
 
 pub fn decode_content(response: SerdeValue) -> Result<String, DecoderError> {
     match &response["content"] {
-        ureq::SerdeValue::String(base_64_content) => match decode(base_64_content) {
-            Ok(decoded_content) => match str::from_utf8(&decoded_content) {
-                Ok(decoded_str) => Ok(decoded_str.to_string()),
-                Err(err) => Err(DecoderError::InvalidContentEncoding(err)),
-            },
-            Err(err) => Err(DecoderError::InvalidContent(err)),
-        },
+        ureq::SerdeValue::String(base_64_content) => {
+            match decode(strip_line_breaks(base_64_content)) {
+                Ok(decoded_content) => match str::from_utf8(&decoded_content) {
+                    Ok(decoded_str) => Ok(decoded_str.to_string()),
+                    Err(err) => Err(DecoderError::InvalidContentEncoding(err)),
+                },
+                Err(err) => Err(DecoderError::InvalidContent(err)),
+            }
+        }
         ureq::SerdeValue::Null => Err(DecoderError::NoContent),
         _ => Err(DecoderError::InvalidContentType(
             response["content"].clone(),
@@ -34,6 +36,14 @@ pub fn decode_content(response: SerdeValue) -> Result<String, DecoderError> {
     }
 }
 
+// GitHub wraps its base64 content every 60 characters
+fn strip_line_breaks(base_64_content: &str) -> String {
+    base_64_content
+        .chars()
+        .filter(|c| *c != '\n' && *c != '\r')
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -53,6 +63,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn can_decode_base64_content_split_across_lines() -> Result<(), Box<dyn std::error::Error>> {
+        let content = encode(b"my content that is long enough to wrap");
+        let (first, second) = content.split_at(20);
+        let mut valid_response = SerdeMap::new();
+        valid_response.insert(
+            "content".to_string(),
+            SerdeValue::String(format!("{}\n{}\n", first, second)),
+        );
+        let json_response = SerdeValue::Object(valid_response);
+
+        let decoded_content = decode_content(json_response)?;
+
+        assert_eq!("my content that is long enough to wrap", decoded_content);
+        Ok(())
+    }
+
     #[test]
     fn cannot_decode_json_that_doesnt_have_content() {
         let json_response = SerdeValue::Object(SerdeMap::new());
@@ -0,0 +1,175 @@
+use super::capture_text::{CaptureText, TodoKeywords};
+use super::dates::Planning;
+use super::markdown::MarkdownFormat;
+use super::metadata::inactive_timestamp;
+use super::org::{escape_body_line, Headline, OrgDocument};
+use super::taskpaper::TaskPaperFormat;
+use super::todo_txt::TodoTxtFormat;
+use chrono::NaiveDateTime;
+use std::fmt;
+
+/// How reminders are written in the inbox file. Every format is read into the
+/// same tree of headlines, the reminders being the ones under the target, and
+/// written back from it.
+pub trait InboxFormat: fmt::Debug + Send {
+    fn parse(&self, text: &str) -> OrgDocument;
+
+    /// Formats without headings have the whole file as the inbox whatever
+    /// the target says.
+    fn has_headings(&self) -> bool {
+        true
+    }
+
+    /// A headline in the target outline, for when the file doesn't have it.
+    fn heading(&self, level: usize, title: &str) -> Headline;
+
+    /// A new reminder at `level`, under a headline at `parent_level`.
+    fn reminder(
+        &self,
+        parent_level: usize,
+        level: usize,
+        text: &CaptureText,
+        planning: Option<&Planning>,
+        keywords: &TodoKeywords,
+    ) -> Headline;
+
+    /// Adds a line of text under the headline that can't be read back as a
+    /// headline.
+    fn push_body_line(&self, headline: &mut Headline, text: &str);
+
+    /// The escaping `push_body_line` does, for text that goes under a
+    /// headline some other way.
+    fn escape_body_line(&self, text: &str) -> String;
+
+    fn property<'a>(&self, headline: &'a Headline, name: &str) -> Option<&'a str>;
+
+    fn set_property(&self, headline: &mut Headline, name: &str, value: &str);
+
+    fn is_done(&self, headline: &Headline, keywords: &TodoKeywords) -> bool;
+
+    fn mark_done(&self, headline: &mut Headline, keywords: &TodoKeywords, closed_at: NaiveDateTime);
+
+    /// Moves the headline and everything under it to `level`.
+    fn set_level(&self, headline: &mut Headline, level: usize);
+}
+
+/// The format for an inbox file, going by its extension. Anything that isn't
+/// Markdown, todo.txt or TaskPaper is org.
+pub fn format_for(path: &str) -> Box<dyn InboxFormat> {
+    let path = path.to_lowercase();
+    if path.ends_with(".md") || path.ends_with(".markdown") {
+        Box::new(MarkdownFormat)
+    } else if path.ends_with(".txt") {
+        Box::new(TodoTxtFormat)
+    } else if path.ends_with(".taskpaper") {
+        Box::new(TaskPaperFormat)
+    } else {
+        Box::new(OrgFormat)
+    }
+}
+
+/// The keyword to write along with a reminder in formats that mark open and
+/// done reminders their own way, which is any but the one new tasks start in
+/// and the done ones.
+pub fn shown_keyword<'a>(text: &'a CaptureText, keywords: &TodoKeywords) -> Option<&'a str> {
+    text.keyword
+        .as_deref()
+        .filter(|keyword| *keyword != keywords.todo() && !keywords.is_done(keyword))
+}
+
+/// `* Inbox` with a `** reminder` headline for each reminder.
+#[derive(Debug)]
+pub struct OrgFormat;
+
+impl InboxFormat for OrgFormat {
+    fn parse(&self, text: &str) -> OrgDocument {
+        OrgDocument::parse(text)
+    }
+
+    fn heading(&self, level: usize, title: &str) -> Headline {
+        Headline::new(level, title)
+    }
+
+    fn reminder(
+        &self,
+        _parent_level: usize,
+        level: usize,
+        text: &CaptureText,
+        planning: Option<&Planning>,
+        _keywords: &TodoKeywords,
+    ) -> Headline {
+        let mut headline = text.headline(level);
+        if let Some(planning) = planning {
+            headline.push_body_line(&planning.to_string());
+        }
+        headline
+    }
+
+    fn push_body_line(&self, headline: &mut Headline, text: &str) {
+        headline.push_body_line(text);
+    }
+
+    fn escape_body_line(&self, text: &str) -> String {
+        escape_body_line(text)
+    }
+
+    fn property<'a>(&self, headline: &'a Headline, name: &str) -> Option<&'a str> {
+        headline.property(name)
+    }
+
+    fn set_property(&self, headline: &mut Headline, name: &str, value: &str) {
+        headline.set_property(name, value);
+    }
+
+    fn is_done(&self, headline: &Headline, keywords: &TodoKeywords) -> bool {
+        keywords
+            .keyword_in(headline.title())
+            .map_or(false, |keyword| keywords.is_done(keyword))
+    }
+
+    fn mark_done(
+        &self,
+        headline: &mut Headline,
+        keywords: &TodoKeywords,
+        closed_at: NaiveDateTime,
+    ) {
+        let level = headline.level();
+        let title = keywords.with_keyword(headline.title(), keywords.done(), level);
+        headline.set_title(&title);
+        headline.close(&inactive_timestamp(closed_at));
+    }
+
+    fn set_level(&self, headline: &mut Headline, level: usize) {
+        headline.set_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_files_are_read_as_markdown() {
+        let document = format_for("gtd/Inbox.MD").parse("## Inbox\n- [ ] one\n");
+
+        assert_eq!("one", document.headlines()[0].children()[0].title());
+    }
+
+    #[test]
+    fn todo_txt_and_taskpaper_files_are_read_as_those() {
+        let document = format_for("todo.txt").parse("(A) one\ntwo\n");
+        assert_eq!(2, document.headlines().len());
+
+        let document = format_for("inbox.taskpaper").parse("Inbox:\n\t- one\n");
+        assert_eq!("one", document.headlines()[0].children()[0].title());
+    }
+
+    #[test]
+    fn anything_else_is_org() {
+        for path in &["inbox.org", "inbox", "notes.md.org"] {
+            let document = format_for(path).parse("* Inbox\n** one\n");
+
+            assert_eq!("one", document.headlines()[0].children()[0].title());
+        }
+    }
+}
@@ -1,19 +1,93 @@
-use super::storage::Storage;
+use super::archive::ArchiveLocation;
+use super::capture_text::{CaptureText, TodoKeywords};
+use super::dates::extract_planning;
+use super::datetree;
+use super::format::{InboxFormat, OrgFormat};
+use super::metadata::{Clock, Metadata, SystemClock};
+use super::org::{Headline, OrgDocument};
+use super::storage::{FileChange, Storage, StorageError};
+use super::target::{Position, Target};
+use super::template::{CaptureTemplate, Note};
+use super::validation::{validate, NoteError};
+use chrono::{NaiveDate, NaiveDateTime};
+use std::slice;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum InboxError {
     #[error("Unable to save reminder {0}")]
-    CouldNotSaveReminder(String),
+    CouldNotSaveReminder(StorageError),
 
-    #[error("Error loading inbox")]
-    FailedToLoad(#[from] anyhow::Error),
+    #[error("Error loading inbox {0}")]
+    FailedToLoad(#[from] StorageError),
+
+    #[error("The inbox changed elsewhere and the reminder could not be added to it {0}")]
+    ConflictNotResolved(StorageError),
+
+    #[error("There is no reminder {0} in the inbox")]
+    NoSuchReminder(usize),
+
+    #[error("The reminder \"{0}\" is no longer in the inbox")]
+    ReminderGone(String),
+
+    #[error("{0}")]
+    InvalidNote(#[from] NoteError),
+}
+
+impl InboxError {
+    pub fn storage_error(&self) -> Option<&StorageError> {
+        match self {
+            InboxError::CouldNotSaveReminder(err) => Some(err),
+            InboxError::FailedToLoad(err) => Some(err),
+            InboxError::ConflictNotResolved(err) => Some(err),
+            InboxError::NoSuchReminder(_) => None,
+            InboxError::ReminderGone(_) => None,
+            InboxError::InvalidNote(_) => None,
+        }
+    }
+}
+
+/// Something to do to a reminder that is already in the inbox.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReminderChange {
+    Edit(String),
+    Delete,
+    MoveUp,
+    MoveDown,
+    Done,
+    Archive,
+}
+
+/// A reminder as it was listed, to find it again after the inbox may have
+/// changed. One with an `:ID:` is found by it wherever it is now, any other
+/// one has to still be at `index` in `reminders()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReminderRef {
+    pub index: usize,
+    pub title: String,
+    pub id: Option<String>,
+}
+
+// A change ready to be written. A reminder archived to another file is
+// carried along to be added to it.
+struct Changed {
+    document: OrgDocument,
+    message: String,
+    archived: Option<Headline>,
 }
 
 #[derive(Debug)]
 pub struct Inbox<T: Storage> {
     storage: T,
-    reminders: Vec<String>,
+    document: OrgDocument,
+    format: Box<dyn InboxFormat>,
+    target: Target,
+    metadata: Metadata,
+    keywords: TodoKeywords,
+    archive: ArchiveLocation,
+    templates: Vec<CaptureTemplate>,
+    clock: Box<dyn Clock>,
+    missing: bool,
 }
 
 impl<T> Inbox<T>
@@ -23,46 +97,588 @@ where
     fn new(storage: T) -> Self {
         Inbox {
             storage,
-            reminders: Vec::new(),
+            document: OrgDocument::parse(""),
+            format: Box::new(OrgFormat),
+            target: Target::default(),
+            metadata: Metadata::default(),
+            keywords: TodoKeywords::default(),
+            archive: ArchiveLocation::default(),
+            templates: Vec::new(),
+            clock: Box::new(SystemClock),
+            missing: false,
         }
     }
 
+    /// The inbox is org unless told otherwise. What was already loaded is
+    /// read again in the new format.
+    pub fn with_format(mut self, format: Box<dyn InboxFormat>) -> Self {
+        self.document = format.parse(&self.document.to_string());
+        self.format = format;
+        self
+    }
+
+    /// Reminders go under `* Inbox` unless told otherwise.
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn with_keywords(mut self, keywords: TodoKeywords) -> Self {
+        self.keywords = keywords;
+        self
+    }
+
+    pub fn with_archive(mut self, archive: ArchiveLocation) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    pub fn with_templates(mut self, templates: Vec<CaptureTemplate>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// A missing inbox file is an empty inbox, and the first save creates it.
     pub fn load(storage: T) -> Result<Self, InboxError> {
-        let raw_inbox = storage
-            .load()
-            .map_err(|err| InboxError::FailedToLoad(err))?;
         let mut inbox = Inbox::new(storage);
-        inbox.reminders = raw_inbox
-            .lines()
-            .map(|line| line.trim())
-            .filter(|line| !line.eq(&"* Inbox"))
-            .filter(|line| !line.is_empty())
-            .map(|reminder| reminder.strip_prefix("** ").unwrap_or(reminder))
-            .map(|reminder| String::from(reminder))
-            .collect();
+        inbox.read().map_err(|err| InboxError::FailedToLoad(err))?;
         Ok(inbox)
     }
 
+    /// The reminder is only kept once storage accepts it, a failed save leaves
+    /// the inbox as it was.
     pub fn save(&mut self, note: &str) -> Result<(), InboxError> {
-        self.reminders.push(note.into());
-        let mut reminder_string = String::from("* Inbox");
-        for reminder in self.reminders.iter() {
-            reminder_string.push_str(format!("\n** {}", reminder).as_str());
+        self.save_note(&Note::from(note))
+    }
+
+    /// Saves the note with its capture template. A template that isn't in
+    /// the settings any more is left out.
+    pub fn save_note(&mut self, note: &Note) -> Result<(), InboxError> {
+        self.save_all(slice::from_ref(note))
+    }
+
+    /// Saves the notes with a single write to storage, all or nothing. Notes
+    /// are validated first, and none are saved if one isn't valid. A note
+    /// whose ID is already in the inbox was saved before and is left out.
+    pub fn save_all(&mut self, notes: &[Note]) -> Result<(), InboxError> {
+        let notes = notes
+            .iter()
+            .map(validate)
+            .collect::<Result<Vec<Note>, NoteError>>()?;
+        let notes = self.unsaved(notes);
+        if notes.is_empty() {
+            return Ok(());
+        }
+        let document = self.with_notes(&notes);
+
+        match self.write(&document, notes.len()) {
+            Ok(_) => {
+                self.document = document;
+                Ok(())
+            }
+            Err(StorageError::Conflict) => self.reload_and_write(&notes),
+            Err(err) => Err(InboxError::CouldNotSaveReminder(err)),
+        }
+    }
+
+    /// Changes the reminder at `index` in `reminders()` and writes the inbox
+    /// with a message saying what happened to it. Moving the first reminder
+    /// up or the last one down, or marking a done reminder done, leaves the
+    /// inbox alone.
+    pub fn change(&mut self, index: usize, change: ReminderChange) -> Result<(), InboxError> {
+        let change = validate_change(change)?;
+        let reminder = self
+            .reminder_headlines()
+            .get(index)
+            .map(|reminder| (*reminder).clone())
+            .ok_or(InboxError::NoSuchReminder(index))?;
+
+        match self.write_change(index, &change) {
+            Ok(_) => Ok(()),
+            Err(StorageError::Conflict) => self.reload_and_change(&reminder, &change),
+            Err(err) => Err(InboxError::CouldNotSaveReminder(err)),
         }
-        self.storage
-            .update(&reminder_string)
-            .map_err(|err| InboxError::CouldNotSaveReminder(format!("{}", err.to_string())))
     }
 
+    /// Where the reminder with this `:ID:` property is in `reminders()`.
+    pub fn index_of(&self, id: &str) -> Option<usize> {
+        self.reminder_headlines()
+            .iter()
+            .position(|reminder| self.format.property(reminder, "ID") == Some(id))
+    }
+
+    /// Where a listed reminder is in `reminders()` now.
+    pub fn find(&self, reminder: &ReminderRef) -> Result<usize, InboxError> {
+        let index = match &reminder.id {
+            Some(id) => self.index_of(id),
+            None => Some(reminder.index).filter(|index| {
+                self.reminder_headlines()
+                    .get(*index)
+                    .map_or(false, |listed| listed.title() == reminder.title)
+            }),
+        };
+        index.ok_or_else(|| InboxError::ReminderGone(reminder.title.clone()))
+    }
+
+    /// The reminders in `reminders()`, to change them by later.
+    pub fn reminder_refs(&self) -> Vec<ReminderRef> {
+        self.reminder_headlines()
+            .iter()
+            .enumerate()
+            .map(|(index, reminder)| ReminderRef {
+                index,
+                title: reminder.title().to_string(),
+                id: self.format.property(reminder, "ID").map(String::from),
+            })
+            .collect()
+    }
+
+    /// The headlines under the target, oldest first whichever end new ones
+    /// are added to.
     pub fn reminders(&self) -> String {
-        self.reminders.join("\n")
+        self.reminder_headlines()
+            .iter()
+            .map(|reminder| reminder.title())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    fn reminder_headlines(&self) -> Vec<&Headline> {
+        let mut reminders = match self.reminder_parent(&self.document) {
+            Some(parent) => parent.children().iter().collect::<Vec<&Headline>>(),
+            None => Vec::new(),
+        };
+        if self.target.position() == Position::Prepend {
+            reminders.reverse();
+        }
+        reminders
+    }
+
+    // The headline reminders go under, today's heading for a datetree.
+    fn reminder_parent<'a>(&self, document: &'a OrgDocument) -> Option<&'a Headline> {
+        let parent = document.outline(self.outline())?;
+        match self.datetree_date() {
+            Some(date) => datetree::day(parent, date),
+            None => Some(parent),
+        }
+    }
+
+    // Like `reminder_parent`, adding the headlines that are missing.
+    fn reminder_parent_mut<'a>(&self, document: &'a mut OrgDocument) -> &'a mut Headline {
+        let format = &*self.format;
+        let parent = document.outline_mut(self.outline(), format);
+        match self.datetree_date() {
+            Some(date) => datetree::day_mut(parent, date, format),
+            None => parent,
+        }
+    }
+
+    // The titles of the headlines reminders are under.
+    fn olpath(&self) -> Vec<String> {
+        let mut olpath = self.target.outline().to_vec();
+        if let Some(date) = self.datetree_date() {
+            olpath.extend(datetree::date_titles(date).iter().cloned());
+        }
+        olpath
+    }
+
+    fn outline(&self) -> &[String] {
+        if self.format.has_headings() {
+            self.target.outline()
+        } else {
+            &[]
+        }
+    }
+
+    fn datetree_date(&self) -> Option<NaiveDate> {
+        Some(self.clock.now().date())
+            .filter(|_| self.target.is_datetree() && self.format.has_headings())
+    }
+
+    fn read(&mut self) -> Result<(), StorageError> {
+        match self.storage.load() {
+            Ok(raw_inbox) => {
+                self.document = self.format.parse(&raw_inbox);
+                self.missing = false;
+            }
+            Err(StorageError::NotFound) => {
+                self.document = self.format.parse("");
+                self.missing = true;
+            }
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, document: &OrgDocument, added: usize) -> Result<(), StorageError> {
+        let reminder_string = document.to_string();
+        if self.missing {
+            self.storage.create(&reminder_string)?;
+            self.missing = false;
+            Ok(())
+        } else if added > 1 {
+            self.storage.update_batch(&reminder_string, added)
+        } else {
+            self.storage.update(&reminder_string)
+        }
+    }
+
+    // New reminders go at either end of what is already under the target
+    // headline. The rest of the file is left as it is.
+    fn with_notes(&self, notes: &[Note]) -> OrgDocument {
+        let mut document = self.document.clone();
+        if notes.is_empty() {
+            return document;
+        }
+        let captured_at = self.clock.now();
+        let format = &*self.format;
+        let parent = self.reminder_parent_mut(&mut document);
+        let parent_level = parent.level();
+        let level = self.target.level_under(parent_level);
+        for note in notes.iter() {
+            let title = note_title(&note.text);
+            let mut body = note_body(&note.text);
+            let mut reminders = match self.template(note) {
+                Some(template) => {
+                    // The rendered template is parsed, so what the user typed
+                    // is escaped as it goes in.
+                    let rendered =
+                        template.render(title, &body, &note.answers, captured_at, &|line| {
+                            format.escape_body_line(line)
+                        });
+                    if template.places_body() {
+                        body.clear();
+                    }
+                    templated_headlines(
+                        format,
+                        parent_level,
+                        level,
+                        &rendered,
+                        &self.keywords,
+                        captured_at,
+                    )
+                }
+                None => vec![note_headline(
+                    format,
+                    parent_level,
+                    level,
+                    title,
+                    &self.keywords,
+                    captured_at,
+                )],
+            };
+            let reminder = &mut reminders[0];
+            for (name, value) in self.metadata.properties(captured_at) {
+                format.set_property(reminder, name, &value);
+            }
+            if let Some(id) = &note.id {
+                format.set_property(reminder, "ID", id);
+            }
+            if let Some(timestamp) = self.metadata.timestamp(captured_at) {
+                format.push_body_line(reminder, &timestamp);
+            }
+            for line in body {
+                format.push_body_line(reminder, line);
+            }
+            for reminder in reminders {
+                match self.target.position() {
+                    Position::Append => parent.push_child(reminder),
+                    Position::Prepend => parent.insert_child(0, reminder),
+                }
+            }
+        }
+        document
+    }
+
+    fn unsaved(&self, notes: Vec<Note>) -> Vec<Note> {
+        notes
+            .into_iter()
+            .filter(|note| {
+                note.id
+                    .as_ref()
+                    .map_or(true, |id| self.index_of(id).is_none())
+            })
+            .collect()
+    }
+
+    fn template(&self, note: &Note) -> Option<&CaptureTemplate> {
+        let name = note.template.as_ref()?;
+        self.templates
+            .iter()
+            .find(|template| template.name == *name)
+    }
+
+    // An archived reminder going to another file is written along with the
+    // inbox, so it is never in both or neither.
+    fn write_change(&mut self, index: usize, change: &ReminderChange) -> Result<(), StorageError> {
+        let changed = match self.with_change(index, change) {
+            Some(changed) => changed,
+            None => return Ok(()),
+        };
+        let inbox = changed.document.to_string();
+
+        match (changed.archived, self.archive.file()) {
+            (Some(reminder), Some(file)) => {
+                let (mut archive, created) = match self.storage.load_file(file) {
+                    Ok(text) => (self.format.parse(&text), false),
+                    Err(StorageError::NotFound) => (self.format.parse(""), true),
+                    Err(err) => return Err(err),
+                };
+                self.archive
+                    .file_into(&mut archive, reminder, &*self.format);
+                let archive = FileChange {
+                    path: file.to_string(),
+                    contents: archive.to_string(),
+                    created,
+                };
+                self.storage
+                    .update_with_files(&inbox, &[archive], &changed.message)?;
+            }
+            _ => self.storage.update_with_message(&inbox, &changed.message)?,
+        }
+        self.document = changed.document;
+        Ok(())
+    }
+
+    // The document with the change made and the message to write it with, or
+    // nothing when the change doesn't change anything.
+    fn with_change(&self, index: usize, change: &ReminderChange) -> Option<Changed> {
+        let mut document = self.document.clone();
+        let format = &*self.format;
+        let prepended = self.target.position() == Position::Prepend;
+        let parent = self.reminder_parent_mut(&mut document);
+        let parent_level = parent.level();
+        let reminders = parent.children_mut();
+        // reminders() lists prepended reminders in reverse
+        let position = |index: usize| {
+            if prepended {
+                reminders.len().checked_sub(index + 1)
+            } else {
+                Some(index).filter(|index| *index < reminders.len())
+            }
+        };
+        let at = position(index)?;
+        let title = reminders[at].title().to_string();
+
+        let message = match change {
+            // Edited text is read the way a saved note's title is, except
+            // for dates, which would need a planning line under it.
+            ReminderChange::Edit(text) => {
+                let edited = format.reminder(
+                    parent_level,
+                    reminders[at].level(),
+                    &CaptureText::parse(text, &self.keywords),
+                    None,
+                    &self.keywords,
+                );
+                let text = edited.title();
+                if text == title {
+                    return None;
+                }
+                reminders[at].set_title(text);
+                format!(
+                    "Reminder \"{}\" changed to \"{}\" from Capture app",
+                    title, text
+                )
+            }
+            ReminderChange::Delete => {
+                reminders.remove(at);
+                format!("Reminder \"{}\" deleted from Capture app", title)
+            }
+            ReminderChange::MoveUp => {
+                let other = position(index.checked_sub(1)?)?;
+                reminders.swap(at, other);
+                format!("Reminder \"{}\" moved up from Capture app", title)
+            }
+            ReminderChange::MoveDown => {
+                let other = position(index + 1)?;
+                reminders.swap(at, other);
+                format!("Reminder \"{}\" moved down from Capture app", title)
+            }
+            ReminderChange::Done => {
+                if format.is_done(&reminders[at], &self.keywords) {
+                    return None;
+                }
+                let line = reminders[at].line().to_string();
+                format.mark_done(&mut reminders[at], &self.keywords, self.clock.now());
+                // Like a Markdown heading, which has no box to check
+                if reminders[at].line() == line {
+                    return None;
+                }
+                format!(
+                    "Reminder \"{}\" marked {} from Capture app",
+                    title,
+                    self.keywords.done()
+                )
+            }
+            ReminderChange::Archive => {
+                let mut reminder = reminders.remove(at);
+                // The same properties org-archive-subtree records
+                let archived_at = self.clock.now().format("%Y-%m-%d %a %H:%M");
+                format.set_property(&mut reminder, "ARCHIVE_TIME", &archived_at.to_string());
+                format.set_property(&mut reminder, "ARCHIVE_OLPATH", &self.olpath().join("/"));
+                if let Some(keyword) = self.keywords.keyword_in(&title) {
+                    format.set_property(&mut reminder, "ARCHIVE_TODO", keyword);
+                }
+                let message = format!("Reminder \"{}\" archived from Capture app", title);
+                if self.archive.file().is_some() {
+                    return Some(Changed {
+                        document,
+                        message,
+                        archived: Some(reminder),
+                    });
+                }
+                self.archive.file_into(&mut document, reminder, format);
+                message
+            }
+        };
+        Some(Changed {
+            document,
+            message,
+            archived: None,
+        })
+    }
+
+    // The reminder may have moved in the inbox that is in storage now, so it
+    // is looked for again. If it was changed elsewhere too it is left alone.
+    fn reload_and_change(
+        &mut self,
+        reminder: &Headline,
+        change: &ReminderChange,
+    ) -> Result<(), InboxError> {
+        self.read()
+            .map_err(|err| InboxError::ConflictNotResolved(err))?;
+
+        let index = self
+            .reminder_headlines()
+            .iter()
+            .position(|current| *current == reminder)
+            .ok_or(InboxError::ConflictNotResolved(StorageError::Conflict))?;
+        self.write_change(index, change)
+            .map_err(|err| InboxError::ConflictNotResolved(err))
+    }
+
+    // Someone else changed the inbox since it was loaded. Start over from what
+    // is in storage now, put the reminders on top and try once more.
+    fn reload_and_write(&mut self, notes: &[Note]) -> Result<(), InboxError> {
+        self.read()
+            .map_err(|err| InboxError::ConflictNotResolved(err))?;
+
+        // The conflict may have been our own write that looked like it failed
+        let notes = self.unsaved(notes.to_vec());
+        if notes.is_empty() {
+            return Ok(());
+        }
+        let document = self.with_notes(&notes);
+        self.write(&document, notes.len())
+            .map_err(|err| InboxError::ConflictNotResolved(err))?;
+        self.document = document;
+        Ok(())
+    }
+}
+
+// The first line of a note is its headline, anything after it is the body.
+fn note_title(note: &str) -> &str {
+    note.lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("")
+}
+
+// A date at the end of the headline is its planning, which the format writes
+// its own way.
+fn note_headline(
+    format: &dyn InboxFormat,
+    parent_level: usize,
+    level: usize,
+    title: &str,
+    keywords: &TodoKeywords,
+    captured_at: NaiveDateTime,
+) -> Headline {
+    let mut text = CaptureText::parse(title, keywords);
+    let (title, planning) = extract_planning(&text.title, captured_at);
+    text.title = title;
+
+    format.reminder(parent_level, level, &text, planning.as_ref(), keywords)
+}
+
+// A template that starts with a headline is written as it is, moved to the
+// level reminders go at. Anything else is read the way a typed note is.
+fn templated_headlines(
+    format: &dyn InboxFormat,
+    parent_level: usize,
+    level: usize,
+    rendered: &str,
+    keywords: &TodoKeywords,
+    captured_at: NaiveDateTime,
+) -> Vec<Headline> {
+    let document = format.parse(rendered);
+    let preamble = document.outline(&[]).map_or(&[][..], |root| root.body());
+    if !document.headlines().is_empty() && preamble.iter().all(|line| line.trim().is_empty()) {
+        return document
+            .headlines()
+            .iter()
+            .cloned()
+            .map(|mut headline| {
+                format.set_level(&mut headline, level);
+                headline
+            })
+            .collect();
+    }
+
+    let title = note_title(rendered);
+    let mut reminder = note_headline(format, parent_level, level, title, keywords, captured_at);
+    for line in note_body(rendered) {
+        format.push_body_line(&mut reminder, line);
+    }
+    vec![reminder]
+}
+
+// An edit is checked like a new note, and replaces only the title, so it has
+// to be a single line.
+fn validate_change(change: ReminderChange) -> Result<ReminderChange, InboxError> {
+    match change {
+        ReminderChange::Edit(text) => {
+            let note = validate(&Note::from(text.as_str()))?;
+            if note.text.contains('\n') {
+                return Err(NoteError::MultipleLines.into());
+            }
+            Ok(ReminderChange::Edit(note.text))
+        }
+        change => Ok(change),
+    }
+}
+
+fn note_body(note: &str) -> Vec<&str> {
+    let mut body = note
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .skip(1)
+        .collect::<Vec<&str>>();
+    while body.last().map_or(false, |line| line.trim().is_empty()) {
+        body.pop();
     }
+    body
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::nodes::inbox::storage::tests::{MockError, MockStorage};
+    use crate::nodes::inbox::markdown::MarkdownFormat;
+    use crate::nodes::inbox::metadata::tests::{capture_time, FixedClock};
+    use crate::nodes::inbox::storage::tests::MockStorage;
+    use crate::nodes::inbox::storage::FileStorage;
+    use crate::nodes::inbox::taskpaper::TaskPaperFormat;
+    use crate::nodes::inbox::todo_txt::TodoTxtFormat;
     use std::rc::Rc;
 
     #[test]
@@ -91,13 +707,16 @@ mod tests {
     #[test]
     fn when_storage_update_fails_pass_along_error() {
         let storage = MockStorage::new()
-            .with_update_error("commit failed")
+            .with_update_error(StorageError::Network("offline".to_string()))
             .as_rc();
         let mut todo = Inbox::new(Rc::clone(&storage));
 
         let result = todo.save(&"whatever you do, don't forget".to_string());
 
-        assert!(result.is_err());
+        assert!(matches!(
+            result,
+            Err(InboxError::CouldNotSaveReminder(StorageError::Network(_)))
+        ));
     }
 
     #[test]
@@ -115,19 +734,40 @@ mod tests {
     }
 
     #[test]
-    fn when_todo_is_loaded_trim_excess_newlines() -> Result<(), InboxError> {
+    fn an_inbox_heading_is_added_when_the_file_has_none() -> Result<(), InboxError> {
         let storage = MockStorage::new().with_inbox("\n** First todo\n").as_rc();
 
         let mut todo = Inbox::load(Rc::clone(&storage))?;
         todo.save(&"second todo".to_string())?;
 
-        assert_eq!("* Inbox\n** First todo\n** second todo", storage.inbox());
+        assert_eq!(
+            "\n** First todo\n* Inbox\n** second todo\n",
+            storage.inbox()
+        );
+        assert_eq!("second todo", todo.reminders());
+
+        Ok(())
+    }
+
+    #[test]
+    fn notes_are_validated_before_saving() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        let empty = todo.save(" \r\n");
+        todo.save("* one \r\n#+TITLE: two\r\n")?;
 
+        assert!(matches!(
+            empty,
+            Err(InboxError::InvalidNote(NoteError::Empty))
+        ));
+        assert_eq!("* Inbox\n** * one\n   ,#+TITLE: two\n", storage.inbox());
+        assert_eq!("* one", todo.reminders());
         Ok(())
     }
 
     #[test]
-    fn when_todo_is_loaded_trim_excess_whitespace() -> Result<(), InboxError> {
+    fn indented_stars_are_left_as_text() -> Result<(), InboxError> {
         let storage = MockStorage::new()
             .with_inbox("* Inbox\n  ** First todo   \n")
             .as_rc();
@@ -135,7 +775,11 @@ mod tests {
         let mut todo = Inbox::load(Rc::clone(&storage))?;
         todo.save(&"second todo".to_string())?;
 
-        assert_eq!("* Inbox\n** First todo\n** second todo", storage.inbox());
+        assert_eq!(
+            "* Inbox\n  ** First todo   \n** second todo\n",
+            storage.inbox()
+        );
+        assert_eq!("second todo", todo.reminders());
 
         Ok(())
     }
@@ -149,21 +793,1008 @@ mod tests {
         let mut todo = Inbox::load(Rc::clone(&storage))?;
         todo.save(&"second todo".to_string())?;
 
-        assert_eq!("* Inbox\n** - First todo\n** second todo", storage.inbox());
+        assert_eq!("* Inbox\n- First todo\n** second todo\n", storage.inbox());
 
         Ok(())
     }
 
     #[test]
-    fn when_todo_list_cant_be_loaded_return_that_result() {
+    fn body_text_and_nested_headlines_stay_with_their_reminder() -> Result<(), InboxError> {
+        let inbox = "* Inbox
+** Call the dentist
+   SCHEDULED: <2026-10-20 Tue>
+   :PROPERTIES:
+   :CREATED:  [2026-10-18 Sun 09:12]
+   :END:
+   Ask about the cleaning
+*** Find the insurance card
+** Buy milk
+";
+        let storage = MockStorage::new().with_inbox(inbox).as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save(&"second todo".to_string())?;
+
+        assert_eq!(format!("{}** second todo\n", inbox), storage.inbox());
+        assert_eq!("Call the dentist\nBuy milk\nsecond todo", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn the_rest_of_the_file_is_kept_around_the_inbox() -> Result<(), InboxError> {
         let storage = MockStorage::new()
-            .with_load_error(MockError::TestFailedToLoad)
+            .with_inbox(
+                "#+TITLE: GTD\n#+FILETAGS: :gtd:\n\n* Inbox\n** one\n\n* Projects\n** Paint\n",
+            )
             .as_rc();
 
-        let todo = Inbox::load(Rc::clone(&storage));
-        match todo {
-            Ok(_) => assert!(false, "Test Failed: Expected load to fail, it succeeded"),
-            Err(err) => assert_eq!("FailedToLoad(Test Failed To Load)", format!("{:?}", err)),
-        }
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save(&"two".to_string())?;
+
+        assert_eq!(
+            "#+TITLE: GTD\n#+FILETAGS: :gtd:\n\n* Inbox\n** one\n\n** two\n* Projects\n** Paint\n",
+            storage.inbox()
+        );
+        assert_eq!("one\ntwo", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn saving_no_notes_writes_the_file_back_unchanged() -> Result<(), InboxError> {
+        let inbox = "#+TITLE: GTD\r\n\r\n* Projects\n  text\n* Inbox  \n** one :tag:\n\n";
+        let storage = MockStorage::new().with_inbox(inbox).as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save_all(&[])?;
+
+        assert_eq!(inbox, storage.inbox());
+        Ok(())
+    }
+
+    #[test]
+    fn reminders_go_under_the_target_outline() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** personal\n* Work\n** Projects\n")
+            .as_rc();
+        let target = Target::new("Work/Inbox", Some(4), Position::Append).unwrap();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?.with_target(target);
+        assert_eq!("", todo.reminders());
+        todo.save(&"one".to_string())?;
+        todo.save(&"two".to_string())?;
+
+        assert_eq!(
+            "* Inbox\n** personal\n* Work\n** Projects\n** Inbox\n**** one\n**** two\n",
+            storage.inbox()
+        );
+        assert_eq!("one\ntwo", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn prepended_reminders_go_above_the_existing_ones() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n  Captured from my phone\n** old\n")
+            .as_rc();
+        let target = Target::new("Inbox", None, Position::Prepend).unwrap();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?.with_target(target);
+        todo.save_all(&["one".into(), "two".into()])?;
+        todo.save(&"three".to_string())?;
+
+        assert_eq!(
+            "* Inbox\n  Captured from my phone\n** three\n** two\n** one\n** old\n",
+            storage.inbox()
+        );
+        assert_eq!("old\none\ntwo\nthree", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn reminders_are_stamped_with_the_capture_time() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+        let metadata = Metadata {
+            timestamp: true,
+            ..Metadata::default()
+        };
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_metadata(metadata)
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.save(&"one".to_string())?;
+
+        assert_eq!(
+            "* Inbox\n** one\n   [2026-10-18 Sun 09:12]\n",
+            storage.inbox()
+        );
+        assert_eq!("one", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn reminders_get_a_property_drawer() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+        let metadata = Metadata {
+            properties: true,
+            captured_from: Some("Android".to_string()),
+            ..Metadata::default()
+        };
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_metadata(metadata)
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.save(&"one".to_string())?;
+
+        let inbox = storage.inbox();
+        let lines = inbox.lines().collect::<Vec<&str>>();
+        assert_eq!(
+            vec![
+                "* Inbox",
+                "** one",
+                "   :PROPERTIES:",
+                "   :CREATED:  [2026-10-18 Sun 09:12]"
+            ],
+            lines[..4].to_vec()
+        );
+        assert!(lines[4].starts_with("   :ID:       "));
+        assert_eq!(
+            vec!["   :CAPTURED_FROM: Android", "   :END:"],
+            lines[5..].to_vec()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn the_lines_after_the_first_are_the_body() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save(
+            &"\nCall the dentist\r\nAsk about:\n  - cleaning\n\n* not a headline\n\n".to_string(),
+        )?;
+        todo.save(&"next".to_string())?;
+
+        assert_eq!(
+            "* Inbox\n** Call the dentist\n   Ask about:\n     - cleaning\n\n   ,* not a headline\n** next\n",
+            storage.inbox()
+        );
+        assert_eq!("Call the dentist\nnext", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn the_body_goes_after_the_metadata() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+        let metadata = Metadata {
+            timestamp: true,
+            ..Metadata::default()
+        };
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_metadata(metadata)
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.save(&"one\nmore about one".to_string())?;
+
+        assert_eq!(
+            "* Inbox\n** one\n   [2026-10-18 Sun 09:12]\n   more about one\n",
+            storage.inbox()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keywords_priorities_and_tags_go_in_the_headline() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_keywords(TodoKeywords::new(&["NEXT", "|", "DONE"]));
+        todo.save(&"NEXT buy milk #errands [#B]\nthe oat one".to_string())?;
+        todo.save(&"TODO is not a keyword here".to_string())?;
+
+        assert_eq!(
+            format!(
+                "* Inbox\n** NEXT [#B] buy milk{}:errands:\n   the oat one\n** TODO is not a keyword here\n",
+                " ".repeat(47)
+            ),
+            storage.inbox()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dates_at_the_end_of_the_headline_become_planning_lines() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+        let metadata = Metadata {
+            timestamp: true,
+            ..Metadata::default()
+        };
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_metadata(metadata)
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.save(&"TODO Renew passport due next friday #travel\nform DS-82".to_string())?;
+        todo.save(&"Meet at 9am".to_string())?;
+
+        assert_eq!(
+            format!(
+                "* Inbox\n** TODO Renew passport{}:travel:\n   DEADLINE: <2026-10-23 Fri>\n   [2026-10-18 Sun 09:12]\n   form DS-82\n** Meet at 9am\n   [2026-10-18 Sun 09:12]\n",
+                " ".repeat(47)
+            ),
+            storage.inbox()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn edits_a_reminder_and_keeps_its_body() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** Call the dentst\n   about the cleaning\n** Buy milk\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.change(0, ReminderChange::Edit("Call the dentist".to_string()))?;
+
+        assert_eq!(
+            "* Inbox\n** Call the dentist\n   about the cleaning\n** Buy milk\n",
+            storage.inbox()
+        );
+        assert_eq!(
+            vec!["Reminder \"Call the dentst\" changed to \"Call the dentist\" from Capture app"],
+            storage.messages()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn edited_reminders_are_written_like_saved_ones() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n** one\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save("TODO Cafe\u{301}  #errands")?;
+        todo.change(
+            0,
+            ReminderChange::Edit("TODO Cafe\u{301}\u{7}  #errands".to_string()),
+        )?;
+        todo.change(
+            1,
+            ReminderChange::Edit("TODO Caf\u{e9}  #errands".to_string()),
+        )?;
+
+        let lines = storage
+            .inbox()
+            .lines()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        assert_eq!(lines[1], lines[2]);
+        assert!(lines[1].starts_with("** TODO Caf\u{e9} "));
+        assert!(lines[1].ends_with(" :errands:"));
+        assert_eq!(1, storage.messages().len());
+        Ok(())
+    }
+
+    #[test]
+    fn an_edit_that_is_not_one_line_of_text_is_an_error() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n** one\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        let empty = todo.change(0, ReminderChange::Edit(" \t".to_string()));
+        let two_lines = todo.change(0, ReminderChange::Edit("one\ntwo".to_string()));
+
+        assert!(matches!(
+            empty,
+            Err(InboxError::InvalidNote(NoteError::Empty))
+        ));
+        assert!(matches!(
+            two_lines,
+            Err(InboxError::InvalidNote(NoteError::MultipleLines))
+        ));
+        assert_eq!("* Inbox\n** one\n", storage.inbox());
+        Ok(())
+    }
+
+    #[test]
+    fn deletes_a_reminder_with_everything_under_it() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** one\n   body\n*** nested\n** two\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.change(0, ReminderChange::Delete)?;
+
+        assert_eq!("* Inbox\n** two\n", storage.inbox());
+        assert_eq!("two", todo.reminders());
+        assert_eq!(
+            vec!["Reminder \"one\" deleted from Capture app"],
+            storage.messages()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn moves_reminders_up_and_down() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** one\n** two\n** three\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.change(2, ReminderChange::MoveUp)?;
+        todo.change(0, ReminderChange::MoveDown)?;
+        todo.change(0, ReminderChange::MoveUp)?;
+        todo.change(2, ReminderChange::MoveDown)?;
+
+        assert_eq!("* Inbox\n** three\n** one\n** two\n", storage.inbox());
+        assert_eq!(2, storage.messages().len());
+        Ok(())
+    }
+
+    #[test]
+    fn prepended_reminders_move_in_the_order_they_are_listed() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** three\n** two\n** one\n")
+            .as_rc();
+        let target = Target::new("Inbox", None, Position::Prepend).unwrap();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?.with_target(target);
+        todo.change(0, ReminderChange::MoveDown)?;
+
+        assert_eq!("two\none\nthree", todo.reminders());
+        assert_eq!("* Inbox\n** three\n** one\n** two\n", storage.inbox());
+        Ok(())
+    }
+
+    #[test]
+    fn reminders_can_be_found_by_id() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** one\n** two\n   :PROPERTIES:\n   :ID:       abc\n   :END:\n")
+            .as_rc();
+
+        let todo = Inbox::load(Rc::clone(&storage))?;
+
+        assert_eq!(Some(1), todo.index_of("abc"));
+        assert_eq!(None, todo.index_of("xyz"));
+        Ok(())
+    }
+
+    #[test]
+    fn listed_reminders_are_found_after_the_inbox_changed() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** one\n** two\n   :PROPERTIES:\n   :ID:       abc\n   :END:\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        let listed = todo.reminder_refs();
+        todo.change(0, ReminderChange::Delete)?;
+
+        assert_eq!(
+            ReminderRef {
+                index: 1,
+                title: "two".to_string(),
+                id: Some("abc".to_string())
+            },
+            listed[1]
+        );
+        assert_eq!(0, todo.find(&listed[1])?);
+        assert!(matches!(
+            todo.find(&listed[0]),
+            Err(InboxError::ReminderGone(title)) if title == "one"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn changing_a_reminder_that_is_not_there_fails() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n** one\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        let result = todo.change(1, ReminderChange::Delete);
+
+        assert!(matches!(result, Err(InboxError::NoSuchReminder(1))));
+        assert!(storage.messages().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn a_change_follows_the_reminder_after_a_conflict() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** one\n** two\n")
+            .with_conflict("* Inbox\n** from emacs\n** one\n** two\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.change(1, ReminderChange::Delete)?;
+
+        assert_eq!("* Inbox\n** from emacs\n** one\n", storage.inbox());
+        Ok(())
+    }
+
+    #[test]
+    fn a_reminder_changed_elsewhere_is_left_alone() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** one\n")
+            .with_conflict("* Inbox\n** one, edited in emacs\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        let result = todo.change(0, ReminderChange::Delete);
+
+        assert!(matches!(result, Err(InboxError::ConflictNotResolved(_))));
+        assert_eq!("* Inbox\n** one, edited in emacs\n", storage.inbox());
+        Ok(())
+    }
+
+    #[test]
+    fn marks_a_reminder_done_with_when_it_was_closed() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** TODO Pay rent\n   SCHEDULED: <2026-10-19 Mon>\n** Buy milk\n")
+            .as_rc();
+
+        let mut todo =
+            Inbox::load(Rc::clone(&storage))?.with_clock(Box::new(FixedClock(capture_time())));
+        todo.change(0, ReminderChange::Done)?;
+        todo.change(1, ReminderChange::Done)?;
+
+        assert_eq!(
+            "* Inbox\n** DONE Pay rent\n   CLOSED: [2026-10-18 Sun 09:12] SCHEDULED: <2026-10-19 Mon>\n** DONE Buy milk\n   CLOSED: [2026-10-18 Sun 09:12]\n",
+            storage.inbox()
+        );
+        assert_eq!(
+            "Reminder \"TODO Pay rent\" marked DONE from Capture app",
+            storage.messages()[0]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn a_reminder_that_is_already_done_is_left_alone() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** CANCELLED Call Bob\n")
+            .as_rc();
+        let keywords = TodoKeywords::new(&["TODO", "|", "DONE", "CANCELLED"]);
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?.with_keywords(keywords);
+        todo.change(0, ReminderChange::Done)?;
+
+        assert!(storage.messages().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn a_markdown_heading_is_never_marked_done() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("## Inbox\n- [ ] one\n### Later\n- [ ] two\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?.with_format(Box::new(MarkdownFormat));
+        todo.change(1, ReminderChange::Done)?;
+
+        assert!(storage.messages().is_empty());
+        assert_eq!("one\nLater", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn archives_a_reminder_to_the_archive_file() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** DONE one\n   CLOSED: [2026-10-17 Sat 18:00]\n** two\n")
+            .as_rc();
+
+        let mut todo =
+            Inbox::load(Rc::clone(&storage))?.with_clock(Box::new(FixedClock(capture_time())));
+        todo.change(0, ReminderChange::Archive)?;
+
+        assert_eq!("* Inbox\n** two\n", storage.inbox());
+        assert_eq!(
+            Some(
+                "* DONE one\n   CLOSED: [2026-10-17 Sat 18:00]\n   :PROPERTIES:\n   :ARCHIVE_TIME: 2026-10-18 Sun 09:12\n   :ARCHIVE_OLPATH: Inbox\n   :ARCHIVE_TODO: DONE\n   :END:"
+                    .to_string()
+            ),
+            storage.file("%s_archive")
+        );
+        assert_eq!(
+            vec!["Reminder \"DONE one\" archived from Capture app"],
+            storage.messages()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn archives_to_a_heading_in_the_inbox() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** one\n** two\n* Archive\n")
+            .as_rc();
+        let archive = ArchiveLocation::new("::* Archive").unwrap();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_archive(archive)
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.change(1, ReminderChange::Archive)?;
+
+        assert!(storage
+            .inbox()
+            .starts_with("* Inbox\n** one\n* Archive\n** two\n   :PROPERTIES:\n"));
+        assert_eq!(None, storage.file("%s_archive"));
+        Ok(())
+    }
+
+    #[test]
+    fn an_archived_reminder_is_archived_once_after_a_conflict() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** one\n")
+            .with_file("archive.org", "* Archive\n")
+            .with_conflict("* Inbox\n** from emacs\n** one\n")
+            .as_rc();
+        let archive = ArchiveLocation::new("archive.org::Archive").unwrap();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?.with_archive(archive);
+        todo.change(0, ReminderChange::Archive)?;
+
+        assert_eq!("* Inbox\n** from emacs\n", storage.inbox());
+        let archived = storage.file("archive.org").unwrap();
+        assert!(archived.starts_with("* Archive\n** one\n"));
+        assert_eq!(1, archived.matches("** one").count());
+        Ok(())
+    }
+
+    #[test]
+    fn when_the_inbox_changed_elsewhere_add_the_reminder_to_the_new_inbox() -> Result<(), InboxError>
+    {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** First todo")
+            .with_conflict("* Inbox\n** First todo\n** Added from emacs")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save(&"second todo".to_string())?;
+
+        assert_eq!(
+            "* Inbox\n** First todo\n** Added from emacs\n** second todo",
+            storage.inbox()
+        );
+        assert_eq!(
+            "First todo\nAdded from emacs\nsecond todo",
+            todo.reminders()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn when_a_save_fails_the_reminder_is_not_kept() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_update_error(StorageError::Network("offline".to_string()))
+            .with_inbox("* Inbox\n** First todo")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        let result = todo.save(&"second todo".to_string());
+
+        assert!(result.is_err());
+        assert_eq!("First todo", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn when_the_inbox_keeps_changing_report_the_conflict() {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox")
+            .with_conflict("* Inbox\n** one")
+            .with_conflict("* Inbox\n** one\n** two")
+            .as_rc();
+
+        let mut todo = Inbox::new(Rc::clone(&storage));
+        let result = todo.save(&"mine".to_string());
+
+        assert!(matches!(result, Err(InboxError::ConflictNotResolved(_))));
+        assert_eq!("* Inbox\n** one\n** two", storage.inbox());
+    }
+
+    #[test]
+    fn saves_reminders_to_a_file_end_to_end() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("inbox.org");
+        std::fs::write(&path, "* Inbox\n** First todo")?;
+
+        let mut todo = Inbox::load(FileStorage::new(path.clone()))?;
+        todo.save(&"second todo".to_string())?;
+
+        assert_eq!(
+            "* Inbox\n** First todo\n** second todo",
+            std::fs::read_to_string(&path)?
+        );
+        assert_eq!("First todo\nsecond todo", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn a_missing_inbox_loads_empty_and_is_created_on_the_first_save() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_missing_inbox().as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        assert_eq!("", todo.reminders());
+        todo.save(&"first todo".to_string())?;
+        todo.save(&"second todo".to_string())?;
+
+        assert!(storage.created());
+        assert_eq!("* Inbox\n** first todo\n** second todo", storage.inbox());
+        Ok(())
+    }
+
+    #[test]
+    fn a_missing_repository_is_not_a_missing_inbox() {
+        let missing = StorageError::MissingRepository("paytonrules/gtd main".to_string());
+        let storage = MockStorage::new().with_load_error(missing.clone()).as_rc();
+
+        let todo = Inbox::load(Rc::clone(&storage));
+
+        assert!(matches!(todo, Err(InboxError::FailedToLoad(err)) if err == missing));
+        assert!(!storage.created());
+    }
+
+    #[test]
+    fn an_inbox_created_elsewhere_in_the_meantime_is_added_to() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_missing_inbox().as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        storage
+            .create(&"* Inbox\n** from emacs".to_string())
+            .unwrap();
+        todo.save(&"mine".to_string())?;
+
+        assert_eq!("* Inbox\n** from emacs\n** mine", storage.inbox());
+        Ok(())
+    }
+
+    #[test]
+    fn creates_a_missing_inbox_file_end_to_end() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("gtd").join("inbox.org");
+
+        let mut todo = Inbox::load(FileStorage::new(path.clone()))?;
+        todo.save(&"first todo".to_string())?;
+
+        assert_eq!("* Inbox\n** first todo", std::fs::read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn save_all_writes_the_notes_at_once() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n** one").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save_all(&["two".into(), "three".into()])?;
+        todo.save(&"four".to_string())?;
+
+        assert_eq!(
+            "* Inbox\n** one\n** two\n** three\n** four",
+            storage.inbox()
+        );
+        assert_eq!(vec![2, 1], storage.writes());
+        Ok(())
+    }
+
+    #[test]
+    fn save_all_reapplies_every_note_after_a_conflict() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox")
+            .with_conflict("* Inbox\n** from emacs")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save_all(&["one".into(), "two".into()])?;
+
+        assert_eq!("* Inbox\n** from emacs\n** one\n** two", storage.inbox());
+        assert_eq!(vec![2], storage.writes());
+        Ok(())
+    }
+
+    #[test]
+    fn a_note_is_saved_with_its_id() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save_note(&Note::from("one").with_id("abc"))?;
+
+        assert_eq!(
+            "* Inbox\n** one\n   :PROPERTIES:\n   :ID:       abc\n   :END:\n",
+            storage.inbox()
+        );
+        assert_eq!(Some(0), todo.index_of("abc"));
+        Ok(())
+    }
+
+    #[test]
+    fn a_note_already_in_the_inbox_is_not_saved_again() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n** one\n   :PROPERTIES:\n   :ID:       abc\n   :END:\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save_all(&[Note::from("one").with_id("abc"), "two".into()])?;
+
+        assert_eq!(
+            "* Inbox\n** one\n   :PROPERTIES:\n   :ID:       abc\n   :END:\n** two\n",
+            storage.inbox()
+        );
+        assert_eq!(vec![1], storage.writes());
+        Ok(())
+    }
+
+    // The write that looked like it failed had gone through
+    #[test]
+    fn a_note_is_not_saved_again_after_a_conflict() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox\n")
+            .with_conflict("* Inbox\n** one\n   :PROPERTIES:\n   :ID:       abc\n   :END:\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?;
+        todo.save_note(&Note::from("one").with_id("abc"))?;
+
+        assert_eq!(
+            "* Inbox\n** one\n   :PROPERTIES:\n   :ID:       abc\n   :END:\n",
+            storage.inbox()
+        );
+        assert!(storage.writes().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn when_todo_list_cant_be_loaded_return_that_result() {
+        let storage = MockStorage::new()
+            .with_load_error(StorageError::Unauthorized)
+            .as_rc();
+
+        let todo = Inbox::load(Rc::clone(&storage));
+        match todo {
+            Ok(_) => assert!(false, "Test Failed: Expected load to fail, it succeeded"),
+            Err(err) => assert_eq!("FailedToLoad(Unauthorized)", format!("{:?}", err)),
+        }
+    }
+
+    #[test]
+    fn saves_reminders_to_a_markdown_inbox() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("# Notes\n\n## Inbox\n- [ ] one\n  - [x] a step\n\n## Later\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_format(Box::new(MarkdownFormat))
+            .with_keywords(TodoKeywords::new(&["TODO", "WAITING", "|", "DONE"]));
+        todo.save("TODO Call mom :family: [#A]\n- bring the list")?;
+        todo.save("WAITING Reply from Bob")?;
+
+        assert_eq!(
+            "# Notes\n\n## Inbox\n- [ ] one\n  - [x] a step\n\n- [ ] [#A] Call mom #family\n  \\- bring the list\n- [ ] WAITING Reply from Bob\n## Later\n",
+            storage.inbox()
+        );
+        assert_eq!(
+            "one\n[#A] Call mom #family\nWAITING Reply from Bob",
+            todo.reminders()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn a_markdown_inbox_gets_an_inbox_heading() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("# Notes\n").as_rc();
+        let metadata = Metadata {
+            timestamp: true,
+            properties: true,
+            ..Metadata::default()
+        };
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_format(Box::new(MarkdownFormat))
+            .with_metadata(metadata)
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.save("one")?;
+
+        let inbox = storage.inbox();
+        let lines = inbox.lines().collect::<Vec<&str>>();
+        assert_eq!(
+            vec![
+                "# Notes",
+                "## Inbox",
+                "- [ ] one",
+                "  created:: [2026-10-18 Sun 09:12]"
+            ],
+            lines[..4].to_vec()
+        );
+        assert!(lines[4].starts_with("  id:: "));
+        assert_eq!(vec!["  [2026-10-18 Sun 09:12]"], lines[5..].to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn markdown_reminders_are_checked_off_and_archived() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("## Inbox\n- [ ] one\n- [ ] two\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_format(Box::new(MarkdownFormat))
+            .with_archive(ArchiveLocation::new("::Archive").unwrap())
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.change(0, ReminderChange::Done)?;
+        todo.change(0, ReminderChange::Done)?;
+        todo.change(0, ReminderChange::Archive)?;
+
+        assert_eq!(
+            "## Inbox\n- [ ] two\n## Archive\n- [x] one ✅ 2026-10-18\n  archive_time:: 2026-10-18 Sun 09:12\n  archive_olpath:: Inbox\n",
+            storage.inbox()
+        );
+        assert_eq!(2, storage.messages().len());
+        Ok(())
+    }
+
+    #[test]
+    fn a_todo_txt_file_is_all_inbox() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("(A) one +garden\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_format(Box::new(TodoTxtFormat))
+            .with_target(Target::new("Projects/Inbox", Some(3), Position::Append).unwrap())
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.save("Renew passport #errands due tomorrow\nat the post office")?;
+        todo.change(0, ReminderChange::Done)?;
+
+        assert_eq!(
+            "x 2026-10-18 one +garden pri:A\nRenew passport @errands due:2026-10-19 at the post office\n",
+            storage.inbox()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn saves_reminders_to_a_taskpaper_inbox() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("Inbox:\n\t- one\nLater:\n")
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_format(Box::new(TaskPaperFormat))
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.save("Call mom #phone [#A]\n- bring the list")?;
+
+        assert_eq!(
+            "Inbox:\n\t- one\n\t- Call mom @phone @priority(A)\n\t\t\\- bring the list\nLater:\n",
+            storage.inbox()
+        );
+        assert_eq!("one\nCall mom @phone @priority(A)", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn a_datetree_files_reminders_under_the_day() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox("* 2026\n** 2026-10 October\n*** 2026-10-19 Monday\n**** later\n")
+            .as_rc();
+        let yesterday = capture_time() - chrono::Duration::days(1);
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_target(Target::datetree("", Position::Append).unwrap())
+            .with_clock(Box::new(FixedClock(yesterday)));
+        todo.save("one")?;
+        let mut todo = todo.with_clock(Box::new(FixedClock(capture_time())));
+        todo.save("two")?;
+        todo.save("three")?;
+
+        assert_eq!(
+            "* 2026\n** 2026-10 October\n*** 2026-10-17 Saturday\n**** one\n*** 2026-10-18 Sunday\n**** two\n**** three\n*** 2026-10-19 Monday\n**** later\n",
+            storage.inbox()
+        );
+        assert_eq!("two\nthree", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn archived_datetree_reminders_record_the_whole_path() -> Result<(), InboxError> {
+        let storage = MockStorage::new()
+            .with_inbox(
+                "* Journal\n** 2026\n*** 2026-10 October\n**** 2026-10-18 Sunday\n***** one\n",
+            )
+            .as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_target(Target::datetree("Journal", Position::Append).unwrap())
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.change(0, ReminderChange::Archive)?;
+
+        assert!(storage
+            .file("%s_archive")
+            .unwrap_or_default()
+            .contains(":ARCHIVE_OLPATH: Journal/2026/2026-10 October/2026-10-18 Sunday\n"));
+        Ok(())
+    }
+
+    fn template(name: &str, template: &str) -> CaptureTemplate {
+        CaptureTemplate {
+            name: name.to_string(),
+            template: template.to_string(),
+        }
+    }
+
+    #[test]
+    fn templates_are_filled_in_and_moved_under_the_target() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+        let todo_template = template(
+            "Todo",
+            "* TODO %? :%^{Context}:\n  :PROPERTIES:\n  :CREATED: %U\n  :END:",
+        );
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_templates(vec![todo_template])
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.save_note(
+            &Note::from("Call mom\nabout the trip")
+                .with_template("Todo", vec!["phone".to_string()]),
+        )?;
+
+        assert_eq!(
+            "* Inbox\n** TODO Call mom :phone:\n  :PROPERTIES:\n  :CREATED: [2026-10-18 Sun 09:12]\n  :END:\n   about the trip\n",
+            storage.inbox()
+        );
+        assert_eq!("TODO Call mom :phone:", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn a_template_without_a_headline_is_read_like_a_typed_note() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_templates(vec![template("Errand", "TODO %? at the %^{Where}\n%i")])
+            .with_clock(Box::new(FixedClock(capture_time())));
+        todo.save_note(
+            &Note::from("Buy milk\n2%").with_template("Errand", vec!["shop".to_string()]),
+        )?;
+        todo.save_note(&Note::from("Call mom").with_template("Removed", Vec::new()))?;
+
+        assert_eq!(
+            "* Inbox\n** TODO Buy milk at the shop\n   2%\n** Call mom\n",
+            storage.inbox()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn template_bodies_are_escaped() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_templates(vec![template("Todo", "** TODO %?\n%i")]);
+        todo.save_note(&Note::from("Call\n* Evil\n#+TITLE: x").with_template("Todo", Vec::new()))?;
+
+        assert_eq!(
+            "* Inbox\n** TODO Call\n,* Evil\n,#+TITLE: x\n",
+            storage.inbox()
+        );
+        assert_eq!("TODO Call", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn template_answers_are_escaped() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("* Inbox\n").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_templates(vec![template("Todo", "** TODO %?\n%^{Where}")]);
+        todo.save_note(&Note::from("Call").with_template("Todo", vec!["* x".to_string()]))?;
+
+        assert_eq!("* Inbox\n** TODO Call\n,* x\n", storage.inbox());
+        assert_eq!("TODO Call", todo.reminders());
+        Ok(())
+    }
+
+    #[test]
+    fn templates_are_written_in_the_inbox_format() -> Result<(), InboxError> {
+        let storage = MockStorage::new().with_inbox("").as_rc();
+
+        let mut todo = Inbox::load(Rc::clone(&storage))?
+            .with_format(Box::new(TodoTxtFormat))
+            .with_templates(vec![template("Call", "(A) %? @phone")]);
+        todo.save_note(&Note::from("mom").with_template("Call", Vec::new()))?;
+
+        assert_eq!("(A) mom @phone", storage.inbox());
+        Ok(())
     }
 }
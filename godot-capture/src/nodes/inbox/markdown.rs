@@ -0,0 +1,403 @@
+use super::capture_text::{CaptureText, TodoKeywords};
+use super::dates::Planning;
+use super::format::{shown_keyword, InboxFormat};
+use super::org::{Headline, OrgDocument};
+use chrono::NaiveDateTime;
+
+/// Markdown the way Obsidian and Logseq write it, an `## Inbox` heading with a
+/// `- [ ] reminder` task for each reminder. `##` and deeper headings are
+/// headlines, and so are task items, one level below the heading or task they
+/// are under. A `#` heading is the note's title, so it is left as text.
+#[derive(Debug)]
+pub struct MarkdownFormat;
+
+impl InboxFormat for MarkdownFormat {
+    fn parse(&self, text: &str) -> OrgDocument {
+        let mut heading_level = 0;
+        let mut open_tasks: Vec<usize> = Vec::new();
+        let mut fenced = false;
+
+        OrgDocument::parse_with(text, |line| {
+            if line.trim_start().starts_with("```") {
+                fenced = !fenced;
+                return None;
+            }
+            if fenced {
+                return None;
+            }
+            if let Some(level) = heading(line) {
+                heading_level = level;
+                open_tasks.clear();
+                return Some((level, level..line.len()));
+            }
+
+            let (indent, title_start) = task(line)?;
+            while open_tasks.last().map_or(false, |open| *open >= indent) {
+                open_tasks.pop();
+            }
+            open_tasks.push(indent);
+            Some((heading_level + open_tasks.len(), title_start..line.len()))
+        })
+    }
+
+    fn heading(&self, level: usize, title: &str) -> Headline {
+        let level = level.max(2);
+        Headline::with_prefix(level, &"#".repeat(level), title)
+    }
+
+    // The checkbox says whether it is done, so the keyword for new tasks and
+    // the done keywords aren't written. Anything else, like WAITING, is.
+    fn reminder(
+        &self,
+        parent_level: usize,
+        level: usize,
+        text: &CaptureText,
+        planning: Option<&Planning>,
+        keywords: &TodoKeywords,
+    ) -> Headline {
+        let done = text
+            .keyword
+            .as_ref()
+            .map_or(false, |keyword| keywords.is_done(keyword));
+        let mut words = Vec::new();
+        if let Some(keyword) = shown_keyword(text, keywords) {
+            words.push(keyword.to_string());
+        }
+        if let Some(priority) = text.priority {
+            words.push(format!("[#{}]", priority));
+        }
+        if !text.title.is_empty() {
+            words.push(text.title.clone());
+        }
+        words.extend(text.tags.iter().map(|tag| format!("#{}", tag)));
+
+        let indent = " ".repeat(2 * level.saturating_sub(parent_level + 1));
+        let checkbox = if done { "[x]" } else { "[ ]" };
+        let mut headline = Headline::with_prefix(
+            level,
+            &format!("{}- {}", indent, checkbox),
+            &words.join(" "),
+        );
+        if let Some(planning) = planning {
+            self.push_body_line(&mut headline, &planning.to_string());
+        }
+        headline
+    }
+
+    // Text that could start a heading or a list item is escaped.
+    fn push_body_line(&self, headline: &mut Headline, text: &str) {
+        let text = self.escape_body_line(text);
+        let line = if text.is_empty() {
+            text
+        } else {
+            format!("{}{}", content_indent(headline), text)
+        };
+        headline.body_mut().push(line);
+    }
+
+    fn escape_body_line(&self, text: &str) -> String {
+        let text = text.trim_end();
+        if text.trim_start().starts_with(|c| "#-*+".contains(c)) {
+            format!("\\{}", text.trim_start())
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn property<'a>(&self, headline: &'a Headline, name: &str) -> Option<&'a str> {
+        headline
+            .body()
+            .iter()
+            .find_map(|line| property_value(line, name))
+    }
+
+    // Properties are written `name:: value` like Logseq does, after the
+    // planning line.
+    fn set_property(&self, headline: &mut Headline, name: &str, value: &str) {
+        let line = format!(
+            "{}{}:: {}",
+            content_indent(headline),
+            name.to_lowercase(),
+            value
+        );
+        let body = headline.body_mut();
+        if let Some(existing) = body
+            .iter()
+            .position(|line| property_value(line, name).is_some())
+        {
+            body[existing] = line;
+            return;
+        }
+        let after = body
+            .iter()
+            .take_while(|line| is_planning(line) || line.contains(":: "))
+            .count();
+        body.insert(after, line);
+    }
+
+    // Headings under the inbox are listed with the tasks but have no box
+    fn is_done(&self, headline: &Headline, _keywords: &TodoKeywords) -> bool {
+        let line = headline.line();
+        if heading(line).is_some() {
+            return false;
+        }
+        let checkbox = &line[..headline.title_range().start];
+        checkbox.ends_with("[x]") || checkbox.ends_with("[X]")
+    }
+
+    // The done date is written the way the Obsidian Tasks plugin does.
+    fn mark_done(
+        &self,
+        headline: &mut Headline,
+        _keywords: &TodoKeywords,
+        closed_at: NaiveDateTime,
+    ) {
+        if heading(headline.line()).is_some() {
+            return;
+        }
+        let title = headline.title_range();
+        let prefix = headline.line()[..title.start].trim_end_matches(|c| c != '[');
+        let line = format!(
+            "{}x]{} ✅ {}",
+            prefix,
+            &headline.line()[title.start..],
+            closed_at.format("%Y-%m-%d")
+        );
+        let level = headline.level();
+        let end = line.len();
+        headline.set_line(level, line, title.start..end);
+    }
+
+    // A task goes at the start of the line however deep it was, taking the
+    // tasks and text under it along.
+    fn set_level(&self, headline: &mut Headline, level: usize) {
+        let dedent = if heading(headline.line()).is_some() {
+            0
+        } else {
+            leading_spaces(headline.line())
+        };
+        move_by(headline, level as isize - headline.level() as isize, dedent);
+    }
+}
+
+fn move_by(headline: &mut Headline, levels: isize, dedent: usize) {
+    let level = (headline.level() as isize + levels).max(1) as usize;
+    let title = headline.title_range();
+    if heading(headline.line()).is_some() {
+        let line = format!("{}{}", "#".repeat(level), &headline.line()[title.start..]);
+        let end = line.len();
+        headline.set_line(level, line, level..end);
+    } else {
+        let removed = leading_spaces(headline.line()).min(dedent);
+        let line = headline.line()[removed..].to_string();
+        let end = line.len();
+        headline.set_line(level, line, title.start - removed..end);
+    }
+
+    for line in headline.body_mut().iter_mut() {
+        let removed = leading_spaces(line).min(dedent);
+        *line = line[removed..].to_string();
+    }
+    for child in headline.children_mut().iter_mut() {
+        move_by(child, levels, dedent);
+    }
+}
+
+// `##` to `######` followed by a space, or nothing
+fn heading(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    match line[level..].chars().next() {
+        Some(' ') | None if (2..=6).contains(&level) => Some(level),
+        _ => None,
+    }
+}
+
+// `- [ ] `, `* [x] ` or `+ [X] ` after any indentation, giving the indentation
+// and where the title starts.
+fn task(line: &str) -> Option<(usize, usize)> {
+    let indent = leading_spaces(line);
+    let rest = &line[indent..];
+    if !rest.starts_with(|c| "-*+".contains(c)) {
+        return None;
+    }
+    let checkbox = rest[1..].strip_prefix(' ')?;
+    if !["[ ]", "[x]", "[X]"]
+        .iter()
+        .any(|checkbox_text| checkbox.starts_with(checkbox_text))
+    {
+        return None;
+    }
+    match checkbox[3..].chars().next() {
+        Some(' ') | None => Some((indent, indent + 5)),
+        _ => None,
+    }
+}
+
+fn leading_spaces(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+// Body text lines up with the text after a task's `- `.
+fn content_indent(headline: &Headline) -> String {
+    if heading(headline.line()).is_some() {
+        String::new()
+    } else {
+        " ".repeat(leading_spaces(headline.line()) + 2)
+    }
+}
+
+fn property_value<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let (key, value) = line.trim().split_once(":: ")?;
+    if key.eq_ignore_ascii_case(name) {
+        Some(value.trim())
+    } else {
+        None
+    }
+}
+
+fn is_planning(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("SCHEDULED:") || line.starts_with("DEADLINE:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn reminders(document: &OrgDocument) -> Vec<&str> {
+        document.headlines()[0]
+            .children()
+            .iter()
+            .map(|reminder| reminder.title())
+            .collect()
+    }
+
+    #[test]
+    fn reads_headings_and_tasks_and_writes_them_back_unchanged() {
+        let text = "# Notes\nsome text\n## Inbox\n- [ ] one\n  - [X] a step\n- plain item\n* [x] two\n```\n- [ ] code\n## code\n```\n### Later\n- [ ] three\n";
+
+        let document = MarkdownFormat.parse(text);
+
+        assert_eq!(text, document.to_string());
+        assert_eq!(vec!["one", "two", "Later"], reminders(&document));
+        let inbox = &document.headlines()[0];
+        assert_eq!("a step", inbox.children()[0].children()[0].title());
+        assert_eq!(
+            vec!["- plain item"],
+            inbox.children()[0].children()[0].body().to_vec()
+        );
+        assert_eq!("three", inbox.children()[2].children()[0].title());
+    }
+
+    #[test]
+    fn hashes_without_a_space_are_not_headings() {
+        let document = MarkdownFormat.parse("#tag\n####### seven\n- [ ]x\n");
+
+        assert!(document.headlines().is_empty());
+    }
+
+    #[test]
+    fn writes_reminders_as_unchecked_tasks() {
+        let keywords = TodoKeywords::new(&["TODO", "NEXT", "|", "DONE"]);
+        let text = CaptureText::parse("TODO Call dentist :health: [#A]", &keywords);
+        let next = CaptureText::parse("NEXT Buy milk", &keywords);
+        let done = CaptureText::parse("DONE Pay rent", &keywords);
+
+        assert_eq!(
+            "- [ ] [#A] Call dentist #health",
+            MarkdownFormat.reminder(2, 3, &text, None, &keywords).line()
+        );
+        assert_eq!(
+            "  - [ ] NEXT Buy milk",
+            MarkdownFormat.reminder(2, 4, &next, None, &keywords).line()
+        );
+        assert_eq!(
+            "- [x] Pay rent",
+            MarkdownFormat.reminder(2, 3, &done, None, &keywords).line()
+        );
+        assert_eq!("## Inbox", MarkdownFormat.heading(1, "Inbox").line());
+    }
+
+    #[test]
+    fn body_lines_are_indented_and_escaped() {
+        let mut reminder = MarkdownFormat.parse("  - [ ] one\n").headlines()[0].clone();
+
+        MarkdownFormat.push_body_line(&mut reminder, "- not a task");
+        MarkdownFormat.push_body_line(&mut reminder, "");
+        MarkdownFormat.push_body_line(&mut reminder, "just text");
+
+        assert_eq!(
+            vec!["    \\- not a task", "", "    just text"],
+            reminder.body().to_vec()
+        );
+    }
+
+    #[test]
+    fn properties_go_after_the_planning_line() {
+        let mut reminder = MarkdownFormat
+            .parse("- [ ] one\n  SCHEDULED: <2026-10-19 Mon>\n  notes\n")
+            .headlines()[0]
+            .clone();
+
+        MarkdownFormat.set_property(&mut reminder, "ID", "1");
+        MarkdownFormat.set_property(&mut reminder, "CREATED", "today");
+        MarkdownFormat.set_property(&mut reminder, "ID", "2");
+
+        assert_eq!(
+            vec![
+                "  SCHEDULED: <2026-10-19 Mon>",
+                "  id:: 2",
+                "  created:: today",
+                "  notes"
+            ],
+            reminder.body().to_vec()
+        );
+        assert_eq!(Some("2"), MarkdownFormat.property(&reminder, "ID"));
+    }
+
+    #[test]
+    fn done_checks_the_box_and_records_the_date() {
+        let keywords = TodoKeywords::default();
+        let mut reminder = MarkdownFormat.parse("  * [ ] one #home\n").headlines()[0].clone();
+        let closed_at = NaiveDate::from_ymd_opt(2026, 10, 18)
+            .and_then(|date| date.and_hms_opt(9, 0, 0))
+            .unwrap();
+
+        assert!(!MarkdownFormat.is_done(&reminder, &keywords));
+        MarkdownFormat.mark_done(&mut reminder, &keywords, closed_at);
+
+        assert_eq!("  * [x] one #home ✅ 2026-10-18", reminder.line());
+        assert!(MarkdownFormat.is_done(&reminder, &keywords));
+    }
+
+    #[test]
+    fn headings_are_never_done() {
+        let keywords = TodoKeywords::default();
+        let document = MarkdownFormat.parse("## Inbox\n### Later\n- [ ] three\n");
+        let mut later = document.headlines()[0].children()[0].clone();
+        let closed_at = NaiveDate::from_ymd_opt(2026, 10, 18)
+            .and_then(|date| date.and_hms_opt(9, 0, 0))
+            .unwrap();
+
+        MarkdownFormat.mark_done(&mut later, &keywords, closed_at);
+
+        assert_eq!("### Later", later.line());
+        assert_eq!("three", later.children()[0].title());
+        assert!(!MarkdownFormat.is_done(&later, &keywords));
+    }
+
+    #[test]
+    fn a_moved_task_takes_what_is_under_it_to_the_start_of_the_line() {
+        let document = MarkdownFormat.parse("## Inbox\n- [ ] one\n  - [ ] step\n    notes\n");
+        let mut reminder = document.headlines()[0].children()[0].children()[0].clone();
+
+        MarkdownFormat.set_level(&mut reminder, 3);
+
+        let mut archive = MarkdownFormat.parse("## Archive\n");
+        archive
+            .outline_mut(&["Archive".to_string()], &MarkdownFormat)
+            .push_child(reminder);
+        assert_eq!("## Archive\n- [ ] step\n  notes\n", archive.to_string());
+    }
+}
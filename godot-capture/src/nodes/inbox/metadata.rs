@@ -0,0 +1,127 @@
+use chrono::{Local, NaiveDateTime};
+use serde::Deserialize;
+use std::fmt;
+use uuid::Uuid;
+
+pub trait Clock: fmt::Debug + Send {
+    fn now(&self) -> NaiveDateTime;
+}
+
+#[derive(Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+/// What gets recorded along with a reminder when it is captured. Nothing is
+/// added unless asked for.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Metadata {
+    #[serde(default)]
+    pub timestamp: bool,
+    #[serde(default)]
+    pub properties: bool,
+    #[serde(default)]
+    pub captured_from: Option<String>,
+}
+
+impl Metadata {
+    /// The properties that go in a reminder's property drawer, or whatever the
+    /// inbox's format has instead.
+    pub fn properties(&self, captured_at: NaiveDateTime) -> Vec<(&'static str, String)> {
+        let mut properties = Vec::new();
+        if self.properties {
+            properties.push(("CREATED", inactive_timestamp(captured_at)));
+            properties.push(("ID", Uuid::new_v4().to_string()));
+            if let Some(captured_from) = &self.captured_from {
+                properties.push(("CAPTURED_FROM", captured_from.clone()));
+            }
+        }
+        properties
+    }
+
+    /// The inactive timestamp that goes under the properties.
+    pub fn timestamp(&self, captured_at: NaiveDateTime) -> Option<String> {
+        Some(inactive_timestamp(captured_at)).filter(|_| self.timestamp)
+    }
+}
+
+pub fn inactive_timestamp(time: NaiveDateTime) -> String {
+    time.format("[%Y-%m-%d %a %H:%M]").to_string()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[derive(Debug)]
+    pub struct FixedClock(pub NaiveDateTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> NaiveDateTime {
+            self.0
+        }
+    }
+
+    pub fn capture_time() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 18)
+            .and_then(|date| date.and_hms_opt(9, 12, 30))
+            .expect("test capture time should be valid")
+    }
+
+    #[test]
+    fn nothing_is_added_by_default() {
+        assert!(Metadata::default().properties(capture_time()).is_empty());
+        assert_eq!(None, Metadata::default().timestamp(capture_time()));
+    }
+
+    #[test]
+    fn the_timestamp_is_inactive_and_has_the_weekday() {
+        let metadata = Metadata {
+            timestamp: true,
+            ..Metadata::default()
+        };
+
+        assert_eq!(
+            Some("[2026-10-18 Sun 09:12]".to_string()),
+            metadata.timestamp(capture_time())
+        );
+        assert!(metadata.properties(capture_time()).is_empty());
+    }
+
+    #[test]
+    fn properties_record_when_and_where_it_was_captured() {
+        let metadata = Metadata {
+            timestamp: true,
+            properties: true,
+            captured_from: Some("Android".to_string()),
+        };
+
+        let properties = metadata.properties(capture_time());
+
+        assert_eq!(
+            ("CREATED", "[2026-10-18 Sun 09:12]".to_string()),
+            properties[0]
+        );
+        assert_eq!("ID", properties[1].0);
+        assert_eq!(("CAPTURED_FROM", "Android".to_string()), properties[2]);
+    }
+
+    #[test]
+    fn every_reminder_gets_its_own_id() {
+        let metadata = Metadata {
+            properties: true,
+            ..Metadata::default()
+        };
+
+        let first = metadata.properties(capture_time());
+        let second = metadata.properties(capture_time());
+
+        assert_eq!(36, first[1].1.len());
+        assert_ne!(first[1], second[1]);
+    }
+}
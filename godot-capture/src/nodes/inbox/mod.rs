@@ -1,6 +1,33 @@
+mod archive;
+mod capture_text;
+mod dates;
+mod datetree;
 mod decoder;
+mod format;
 mod inbox;
+mod markdown;
+mod metadata;
+mod org;
+mod outbox;
 pub mod storage;
+mod target;
+mod taskpaper;
+mod template;
+mod todo_txt;
+mod validation;
+mod worker;
+pub use archive::ArchiveLocation;
+pub use capture_text::TodoKeywords;
+pub use format::{format_for, InboxFormat};
 pub use inbox::Inbox;
-pub use inbox::InboxError;
-pub use storage::{GitlabStorage, Storage};
+pub use inbox::{InboxError, ReminderChange, ReminderRef};
+pub use metadata::{Clock, Metadata, SystemClock};
+pub use outbox::{Outbox, OutboxError};
+pub use storage::{
+    FileConfig, FileStorage, GitRepoConfig, GitRepoStorage, GithubConfig, GithubStorage,
+    GitlabConfig, GitlabStorage, PathTemplate, Storage, StorageError, TemplatedStorage,
+};
+pub use target::{Position, Target};
+pub use template::{CaptureTemplate, Note};
+pub use validation::{validate, NoteError};
+pub use worker::{InboxWorker, WorkerEvent};
@@ -0,0 +1,660 @@
+use super::format::InboxFormat;
+use std::fmt;
+use std::ops::Range;
+
+/// An org headline with everything under it. The headline and body lines are
+/// kept exactly as they were read so writing an unchanged tree gives back the
+/// same text. Other formats keep their own lines in the same tree, with the
+/// title being whatever part of the line they say it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Headline {
+    level: usize,
+    line: String,
+    title_range: Range<usize>,
+    body: Vec<String>,
+    children: Vec<Headline>,
+}
+
+impl Headline {
+    pub fn new(level: usize, title: &str) -> Self {
+        Headline::with_prefix(level, &"*".repeat(level), title)
+    }
+
+    /// A headline written as `prefix` followed by its title.
+    pub fn with_prefix(level: usize, prefix: &str, title: &str) -> Self {
+        let line = format!("{}{}{}", prefix, separator(prefix), title);
+        let title_range = prefix.len()..line.len();
+        Headline::with_line(level, line, title_range)
+    }
+
+    /// A headline written as `line`, with its title at `title_range`.
+    pub fn with_line(level: usize, line: String, title_range: Range<usize>) -> Self {
+        Headline {
+            level,
+            line,
+            title_range,
+            body: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Replaces the headline's line, for formats that mark headlines their
+    /// own way.
+    pub fn set_line(&mut self, level: usize, line: String, title_range: Range<usize>) {
+        self.level = level;
+        self.line = line;
+        self.title_range = title_range;
+    }
+
+    /// Where the title is in the headline's line, the way the format reads
+    /// it, which may have spaces around it.
+    pub fn title_range(&self) -> Range<usize> {
+        self.title_range.clone()
+    }
+
+    pub fn title(&self) -> &str {
+        self.line[self.title_range.clone()].trim()
+    }
+
+    /// Replaces the title, keeping whatever is around it on the line.
+    pub fn set_title(&mut self, title: &str) {
+        let prefix = &self.line[..self.title_range.start];
+        let suffix = &self.line[self.title_range.end..];
+        let title = format!("{}{}", separator(prefix), title);
+        let start = prefix.len();
+        self.line = format!("{}{}{}", prefix, title, suffix);
+        self.title_range = start..start + title.len();
+    }
+
+    pub fn body(&self) -> &[String] {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut Vec<String> {
+        &mut self.body
+    }
+
+    /// Moves the headline and everything under it to another level, keeping
+    /// the children the same distance below it.
+    pub fn set_level(&mut self, level: usize) {
+        let rest = self.line[self.level..].to_string();
+        for child in self.children.iter_mut() {
+            child.set_level(level + child.level - self.level);
+        }
+        self.level = level;
+        self.line = format!("{}{}", "*".repeat(level), rest);
+        self.title_range = level..self.line.len();
+    }
+
+    /// Adds `CLOSED: timestamp` to the planning line right under the
+    /// headline, making one if there isn't one.
+    pub fn close(&mut self, timestamp: &str) {
+        let closed = format!("CLOSED: {}", timestamp);
+        match self.planning_line() {
+            Some(planning) => {
+                let text = self.body[planning].trim().to_string();
+                self.body[planning] = self.indented(&format!("{} {}", closed, text));
+            }
+            None => {
+                let line = self.indented(&closed);
+                self.body.insert(0, line);
+            }
+        }
+    }
+
+    /// Sets a property in the headline's drawer, which goes after the
+    /// planning line and is added if the headline has none.
+    pub fn set_property(&mut self, name: &str, value: &str) {
+        let key = format!(":{}:", name.to_uppercase());
+        let line = self.indented(&format!("{:<10} {}", key, value));
+        let start = match self
+            .body
+            .iter()
+            .position(|line| line.trim() == ":PROPERTIES:")
+        {
+            Some(start) => start,
+            None => {
+                let start = self.planning_line().map_or(0, |planning| planning + 1);
+                let drawer = vec![self.indented(":PROPERTIES:"), self.indented(":END:")];
+                self.body.splice(start..start, drawer);
+                start
+            }
+        };
+
+        let mut at = start + 1;
+        while at < self.body.len() && self.body[at].trim() != ":END:" {
+            if self.body[at].trim().starts_with(key.as_str()) {
+                self.body[at] = line;
+                return;
+            }
+            at += 1;
+        }
+        self.body.insert(at, line);
+    }
+
+    /// The value of a property in the headline's drawer.
+    pub fn property(&self, name: &str) -> Option<&str> {
+        let key = format!(":{}:", name.to_uppercase());
+        self.body
+            .iter()
+            .map(|line| line.trim())
+            .skip_while(|line| *line != ":PROPERTIES:")
+            .take_while(|line| *line != ":END:")
+            .find_map(|line| line.strip_prefix(key.as_str()))
+            .map(|value| value.trim())
+    }
+
+    pub fn children(&self) -> &[Headline] {
+        &self.children
+    }
+
+    pub fn children_mut(&mut self) -> &mut Vec<Headline> {
+        &mut self.children
+    }
+
+    /// Adds a line of text under the headline, indented to line up with its
+    /// title. Text starting with a star or a `#+` keyword is escaped with a
+    /// comma, the way org escapes it in blocks, so it can't be read back as a
+    /// headline or a setting.
+    pub fn push_body_line(&mut self, text: &str) {
+        let text = escape_body_line(text);
+        if text.is_empty() {
+            self.body.push(text);
+        } else {
+            self.body
+                .push(format!("{}{}", " ".repeat(self.level + 1), text));
+        }
+    }
+
+    // SCHEDULED, DEADLINE and CLOSED have to be on the line after the
+    // headline.
+    fn planning_line(&self) -> Option<usize> {
+        let first = self.body.first()?.trim_start();
+        if ["SCHEDULED:", "DEADLINE:", "CLOSED:"]
+            .iter()
+            .any(|keyword| first.starts_with(keyword))
+        {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    fn indented(&self, text: &str) -> String {
+        format!("{}{}", " ".repeat(self.level + 1), text)
+    }
+
+    pub fn push_child(&mut self, child: Headline) {
+        self.children.push(child);
+    }
+
+    pub fn insert_child(&mut self, index: usize, child: Headline) {
+        self.children.insert(index, child);
+    }
+
+    fn write_lines<'a>(&'a self, lines: &mut Vec<&'a str>) {
+        lines.push(&self.line);
+        lines.extend(self.body.iter().map(String::as_str));
+        for child in self.children.iter() {
+            child.write_lines(lines);
+        }
+    }
+}
+
+// Titles follow whatever marks the line as a headline after a space, unless
+// nothing or only indentation does.
+fn separator(prefix: &str) -> &'static str {
+    if prefix.chars().last().map_or(true, char::is_whitespace) {
+        ""
+    } else {
+        " "
+    }
+}
+
+/// An org file as a tree under a root that isn't written, with the text
+/// before the first headline as the root's body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrgDocument {
+    root: Headline,
+    ends_with_newline: bool,
+}
+
+/// A line of text with a comma before a star or a `#+` keyword it starts with.
+pub fn escape_body_line(text: &str) -> String {
+    let text = text.trim_end();
+    let indent = text.len() - text.trim_start().len();
+    if text.starts_with('*') || text[indent..].starts_with("#+") {
+        format!("{},{}", &text[..indent], &text[indent..])
+    } else {
+        text.to_string()
+    }
+}
+
+impl OrgDocument {
+    pub fn parse(text: &str) -> Self {
+        OrgDocument::parse_with(text, |line| {
+            headline_level(line).map(|level| (level, level..line.len()))
+        })
+    }
+
+    /// Reads a file into the tree, with `headline` saying which lines are
+    /// headlines by giving their level and where their title is. It is
+    /// called on every line in order.
+    pub fn parse_with<F>(text: &str, headline: F) -> Self
+    where
+        F: FnMut(&str) -> Mark,
+    {
+        let ends_with_newline = text.ends_with('\n');
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        let lines = if text.is_empty() && !ends_with_newline {
+            Vec::new()
+        } else {
+            text.split('\n').collect::<Vec<&str>>()
+        };
+        let marks = lines.iter().copied().map(headline).collect::<Vec<Mark>>();
+
+        let mut next = 0;
+        let mut root = Headline::with_line(0, String::new(), 0..0);
+        root.body = take_body(&lines, &marks, &mut next);
+        root.children = take_children(&lines, &marks, &mut next, 0);
+
+        OrgDocument {
+            root,
+            ends_with_newline,
+        }
+    }
+
+    pub fn headlines(&self) -> &[Headline] {
+        &self.root.children
+    }
+
+    pub fn push_headline(&mut self, headline: Headline) {
+        self.root.push_child(headline);
+    }
+
+    /// The headline at the end of a path of titles, each one a child of the
+    /// one before. An empty path is the root, the whole file.
+    pub fn outline(&self, path: &[String]) -> Option<&Headline> {
+        let mut headline = &self.root;
+        for title in path.iter() {
+            headline = headline
+                .children
+                .iter()
+                .find(|child| child.title() == title)?;
+        }
+        Some(headline)
+    }
+
+    /// Like `outline`, but adds whatever part of the path is missing at the
+    /// end of its parent, one level below it.
+    pub fn outline_mut(&mut self, path: &[String], format: &dyn InboxFormat) -> &mut Headline {
+        let mut headline = &mut self.root;
+        for title in path.iter() {
+            headline = find_or_add_child(headline, title, format);
+        }
+        headline
+    }
+}
+
+fn find_or_add_child<'a>(
+    parent: &'a mut Headline,
+    title: &str,
+    format: &dyn InboxFormat,
+) -> &'a mut Headline {
+    let position = match parent
+        .children
+        .iter()
+        .position(|headline| headline.title() == title)
+    {
+        Some(position) => position,
+        None => {
+            let heading = format.heading(parent.level + 1, title);
+            parent.children.push(heading);
+            parent.children.len() - 1
+        }
+    };
+    &mut parent.children[position]
+}
+
+impl fmt::Display for OrgDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines = self
+            .root
+            .body
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<&str>>();
+        for headline in self.root.children.iter() {
+            headline.write_lines(&mut lines);
+        }
+
+        write!(f, "{}", lines.join("\n"))?;
+        if self.ends_with_newline {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// A headline is one or more stars at the very start of a line followed by a
+// space, anything else is body text.
+fn headline_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '*').count();
+    match line[level..].chars().next() {
+        Some(' ') if level > 0 => Some(level),
+        None if level > 0 => Some(level),
+        _ => None,
+    }
+}
+
+type Mark = Option<(usize, Range<usize>)>;
+
+fn take_body(lines: &[&str], marks: &[Mark], next: &mut usize) -> Vec<String> {
+    let mut body = Vec::new();
+    while *next < lines.len() && marks[*next].is_none() {
+        body.push(lines[*next].to_string());
+        *next += 1;
+    }
+    body
+}
+
+fn take_children(
+    lines: &[&str],
+    marks: &[Mark],
+    next: &mut usize,
+    parent_level: usize,
+) -> Vec<Headline> {
+    let mut children = Vec::new();
+    while let Some((level, title_range)) = marks.get(*next).cloned().flatten() {
+        if level <= parent_level {
+            break;
+        }
+        let line = lines[*next].to_string();
+        *next += 1;
+        let body = take_body(lines, marks, next);
+        let grandchildren = take_children(lines, marks, next, level);
+        children.push(Headline {
+            level,
+            line,
+            title_range,
+            body,
+            children: grandchildren,
+        });
+    }
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::format::OrgFormat;
+
+    const FULL_INBOX: &str = "#+TITLE: GTD
+#+STARTUP: overview
+
+* Inbox
+** Call the dentist
+   SCHEDULED: <2026-10-20 Tue>
+   :PROPERTIES:
+   :CREATED:  [2026-10-18 Sun 09:12]
+   :END:
+   Ask about the cleaning
+*** Find the insurance card
+** Buy milk\t:errands:
+* Projects
+** Paint the fence
+";
+
+    #[test]
+    fn an_unchanged_document_is_written_back_exactly() {
+        for text in &[
+            FULL_INBOX,
+            "",
+            "\n",
+            "* Inbox",
+            "* Inbox\n\n\n",
+            "* Inbox\r\n** one\r\n",
+            "no headlines at all\n",
+            "** starts deeper\n* then shallower\n",
+        ] {
+            assert_eq!(*text, OrgDocument::parse(text).to_string());
+        }
+    }
+
+    #[test]
+    fn body_lines_and_nested_headlines_stay_under_their_headline() {
+        let document = OrgDocument::parse(FULL_INBOX);
+
+        let inbox = &document.headlines()[0];
+        let titles = inbox
+            .children()
+            .iter()
+            .map(|child| child.title())
+            .collect::<Vec<&str>>();
+        assert_eq!(vec!["Call the dentist", "Buy milk\t:errands:"], titles);
+        assert_eq!(
+            "Find the insurance card",
+            inbox.children()[0].children()[0].title()
+        );
+        assert_eq!(2, document.headlines().len());
+    }
+
+    #[test]
+    fn stars_without_a_space_or_indented_are_not_headlines() {
+        let document = OrgDocument::parse("* Inbox\n**bold** text\n  ** indented\n");
+
+        assert_eq!(1, document.headlines().len());
+        assert!(document.headlines()[0].children().is_empty());
+    }
+
+    fn path(outline: &str) -> Vec<String> {
+        outline.split('/').map(String::from).collect()
+    }
+
+    #[test]
+    fn new_children_are_added_after_the_existing_subtree() {
+        let mut document = OrgDocument::parse("* Inbox\n** one\n   body\n*** nested\n* Projects\n");
+
+        document
+            .outline_mut(&path("Inbox"), &OrgFormat)
+            .push_child(Headline::new(2, "two"));
+
+        assert_eq!(
+            "* Inbox\n** one\n   body\n*** nested\n** two\n* Projects\n",
+            document.to_string()
+        );
+    }
+
+    #[test]
+    fn children_can_be_added_before_the_existing_ones() {
+        let mut document = OrgDocument::parse("* Inbox\n  notes\n** one\n");
+
+        document
+            .outline_mut(&path("Inbox"), &OrgFormat)
+            .insert_child(0, Headline::new(2, "two"));
+
+        assert_eq!("* Inbox\n  notes\n** two\n** one\n", document.to_string());
+    }
+
+    #[test]
+    fn body_lines_are_indented_under_the_title() {
+        let mut document = OrgDocument::parse("* Inbox\n");
+        let mut reminder = Headline::new(2, "one");
+        reminder.push_body_line("text");
+        reminder.push_body_line("");
+
+        document
+            .outline_mut(&path("Inbox"), &OrgFormat)
+            .push_child(reminder);
+
+        assert_eq!("* Inbox\n** one\n   text\n\n", document.to_string());
+    }
+
+    #[test]
+    fn body_lines_never_become_headlines() {
+        let mut reminder = Headline::new(2, "one");
+        reminder.push_body_line("* two");
+        reminder.push_body_line("  ** three  ");
+        let mut document = OrgDocument::parse("* Inbox\n");
+
+        document
+            .outline_mut(&path("Inbox"), &OrgFormat)
+            .push_child(reminder);
+        let written = document.to_string();
+
+        assert_eq!("* Inbox\n** one\n   ,* two\n     ** three\n", written);
+        let reparsed = OrgDocument::parse(&written);
+        assert_eq!(1, reparsed.headlines()[0].children().len());
+        assert!(reparsed.headlines()[0].children()[0].children().is_empty());
+    }
+
+    #[test]
+    fn body_lines_never_become_settings() {
+        let mut reminder = Headline::new(2, "one");
+        reminder.push_body_line("#+TITLE: two");
+        reminder.push_body_line("  #+begin_src");
+
+        assert_eq!(
+            vec!["   ,#+TITLE: two", "     ,#+begin_src"],
+            reminder.body().to_vec()
+        );
+    }
+
+    #[test]
+    fn retitling_keeps_the_body_and_children() {
+        let mut document = OrgDocument::parse("* Inbox\n** one\n   body\n*** nested\n");
+
+        document
+            .outline_mut(&path("Inbox"), &OrgFormat)
+            .children_mut()[0]
+            .set_title("uno");
+
+        assert_eq!(
+            "* Inbox\n** uno\n   body\n*** nested\n",
+            document.to_string()
+        );
+    }
+
+    #[test]
+    fn reads_properties_from_the_drawer() {
+        let document = OrgDocument::parse(
+            "* Inbox\n  :PROPERTIES:\n  :ID:       abc-123\n  :CREATED:  [2026-10-18 Sun]\n  :END:\n",
+        );
+        let inbox = &document.headlines()[0];
+
+        assert_eq!(Some("abc-123"), inbox.property("ID"));
+        assert_eq!(Some("[2026-10-18 Sun]"), inbox.property("created"));
+        assert_eq!(None, inbox.property("CAPTURED_FROM"));
+    }
+
+    #[test]
+    fn outlines_follow_the_headline_tree() {
+        let document = OrgDocument::parse("* Inbox\n* Work\n** Inbox\n*** one\n");
+
+        let inbox = document.outline(&path("Work/Inbox"));
+
+        assert_eq!(Some(2), inbox.map(|inbox| inbox.level()));
+        assert!(document.outline(&path("Inbox/Work")).is_none());
+        assert!(document.outline(&path("Inbox")).is_some());
+    }
+
+    #[test]
+    fn the_missing_part_of_an_outline_is_added() {
+        let mut document = OrgDocument::parse("#+TITLE: GTD\n* Work\n** Projects\n");
+
+        document
+            .outline_mut(&path("Work/Inbox"), &OrgFormat)
+            .push_child(Headline::new(3, "one"));
+        document
+            .outline_mut(&path("Home/Inbox"), &OrgFormat)
+            .push_child(Headline::new(3, "two"));
+
+        assert_eq!(
+            "#+TITLE: GTD\n* Work\n** Projects\n** Inbox\n*** one\n* Home\n** Inbox\n*** two\n",
+            document.to_string()
+        );
+    }
+
+    #[test]
+    fn closing_joins_the_planning_line() {
+        let mut document = OrgDocument::parse(FULL_INBOX);
+        let inbox = document.outline_mut(&path("Inbox"), &OrgFormat);
+
+        inbox.children_mut()[0].close("[2026-10-18 Sun 10:00]");
+        inbox.children_mut()[1].close("[2026-10-18 Sun 10:05]");
+
+        let text = document.to_string();
+        assert!(text.contains(
+            "** Call the dentist\n   CLOSED: [2026-10-18 Sun 10:00] SCHEDULED: <2026-10-20 Tue>\n"
+        ));
+        assert!(text.contains("** Buy milk\t:errands:\n   CLOSED: [2026-10-18 Sun 10:05]\n"));
+    }
+
+    #[test]
+    fn properties_go_in_the_drawer_after_the_planning_line() {
+        let mut document = OrgDocument::parse(FULL_INBOX);
+        let inbox = document.outline_mut(&path("Inbox"), &OrgFormat);
+
+        inbox.children_mut()[0].set_property("archive_time", "2026-10-18 Sun 10:00");
+        inbox.children_mut()[0].set_property("created", "[2026-10-19 Mon 08:00]");
+        inbox.children_mut()[1].set_property("ID", "abc");
+
+        let dentist = &inbox.children()[0];
+        assert_eq!(
+            Some("2026-10-18 Sun 10:00"),
+            dentist.property("ARCHIVE_TIME")
+        );
+        assert_eq!(Some("[2026-10-19 Mon 08:00]"), dentist.property("CREATED"));
+        let text = document.to_string();
+        assert!(text.contains(
+            "   SCHEDULED: <2026-10-20 Tue>\n   :PROPERTIES:\n   :CREATED:  [2026-10-19 Mon 08:00]\n   :ARCHIVE_TIME: 2026-10-18 Sun 10:00\n   :END:\n"
+        ));
+        assert!(
+            text.contains("** Buy milk\t:errands:\n   :PROPERTIES:\n   :ID:       abc\n   :END:\n")
+        );
+    }
+
+    #[test]
+    fn changing_the_level_moves_the_whole_subtree() {
+        let document = OrgDocument::parse(FULL_INBOX);
+        let mut dentist = document.headlines()[0].children()[0].clone();
+
+        dentist.set_level(1);
+
+        let mut moved = OrgDocument::parse("");
+        moved.push_headline(dentist);
+        assert!(moved
+            .to_string()
+            .starts_with("* Call the dentist\n   SCHEDULED"));
+        assert!(moved.to_string().ends_with("\n** Find the insurance card"));
+    }
+
+    #[test]
+    fn new_and_retitled_headlines_read_back_the_same() {
+        let mut document = OrgDocument::parse("");
+        let mut headline = Headline::new(1, "Inbox");
+        headline.push_child(Headline::new(2, "one"));
+        headline.children_mut()[0].set_title("two");
+        document.push_headline(headline);
+
+        assert_eq!(OrgDocument::parse(&document.to_string()), document);
+    }
+
+    #[test]
+    fn an_empty_outline_is_the_whole_file() {
+        let document = OrgDocument::parse("text\n* one\n");
+
+        let root = document.outline(&[]).unwrap();
+
+        assert_eq!(0, root.level());
+        assert_eq!("one", root.children()[0].title());
+    }
+}
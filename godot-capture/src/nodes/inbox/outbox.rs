@@ -0,0 +1,316 @@
+use super::inbox::{Inbox, InboxError};
+use super::storage::Storage;
+use super::template::Note;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum OutboxError {
+    #[error("Unable to read the unsent reminders {0}")]
+    Unreadable(io::Error),
+
+    #[error("The unsent reminders file is corrupt {0}")]
+    Corrupt(#[from] serde_json::Error),
+
+    #[error("Unable to store the reminder for later {0}")]
+    Unwritable(io::Error),
+
+    #[error("{0}")]
+    CouldNotDeliver(InboxError),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedReminder {
+    #[serde(flatten)]
+    pub note: Note,
+    pub queued_at: u64,
+}
+
+/// Reminders that have been captured but not saved to storage yet, kept on
+/// disk so they survive the app closing while offline.
+#[derive(Debug)]
+pub struct Outbox {
+    path: PathBuf,
+    queue: Vec<QueuedReminder>,
+}
+
+impl Outbox {
+    pub fn open(path: &Path) -> Result<Self, OutboxError> {
+        let queue = match fs::read_to_string(path) {
+            Ok(raw_queue) => serde_json::from_str(&raw_queue)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(OutboxError::Unreadable(err)),
+        };
+
+        Ok(Outbox {
+            path: path.to_path_buf(),
+            queue,
+        })
+    }
+
+    /// Queues the note with an ID, so it isn't saved twice if the outbox
+    /// can't be written after it was.
+    pub fn push(&mut self, note: Note, queued_at: u64) -> Result<(), OutboxError> {
+        let note = match note.id {
+            Some(_) => note,
+            None => note.with_id(&Uuid::new_v4().to_string()),
+        };
+        self.queue.push(QueuedReminder { note, queued_at });
+        self.persist().map_err(|err| {
+            self.queue.pop();
+            err
+        })
+    }
+
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Saves queued reminders oldest first, stopping at the first one storage
+    /// won't take so the order is kept for the next attempt.
+    pub fn replay<T: Storage>(&mut self, inbox: &mut Inbox<T>) -> Result<(), OutboxError> {
+        while let Some(reminder) = self.queue.first() {
+            inbox
+                .save_note(&reminder.note)
+                .map_err(|err| OutboxError::CouldNotDeliver(err))?;
+            self.queue.remove(0);
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    /// Saves everything queued in a single write, or leaves it all queued.
+    pub fn replay_batched<T: Storage>(&mut self, inbox: &mut Inbox<T>) -> Result<(), OutboxError> {
+        if self.queue.is_empty() {
+            return Ok(());
+        }
+
+        let notes = self
+            .queue
+            .iter()
+            .map(|reminder| reminder.note.clone())
+            .collect::<Vec<Note>>();
+        inbox
+            .save_all(&notes)
+            .map_err(|err| OutboxError::CouldNotDeliver(err))?;
+        self.queue.clear();
+        self.persist()
+    }
+
+    fn persist(&self) -> Result<(), OutboxError> {
+        let raw_queue = serde_json::to_string(&self.queue)?;
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, raw_queue)
+            .and_then(|_| fs::rename(&temp_path, &self.path))
+            .map_err(|err| OutboxError::Unwritable(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::storage::tests::MockStorage;
+    use crate::nodes::inbox::storage::StorageError;
+    use std::rc::Rc;
+    use tempfile::TempDir;
+
+    fn outbox_path() -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().expect("Could not create temp dir");
+        let path = dir.path().join("outbox.json");
+        (dir, path)
+    }
+
+    #[test]
+    fn a_missing_outbox_file_is_empty() -> Result<(), OutboxError> {
+        let (_dir, path) = outbox_path();
+
+        let outbox = Outbox::open(&path)?;
+
+        assert_eq!(0, outbox.pending());
+        Ok(())
+    }
+
+    #[test]
+    fn queued_reminders_survive_reopening() -> Result<(), OutboxError> {
+        let (_dir, path) = outbox_path();
+
+        let mut outbox = Outbox::open(&path)?;
+        outbox.push(Note::from("first").with_id("1"), 100)?;
+        outbox.push(
+            Note::from("second")
+                .with_template("Todo", vec!["Ana".to_string()])
+                .with_id("2"),
+            200,
+        )?;
+        let reopened = Outbox::open(&path)?;
+
+        assert_eq!(
+            vec![
+                QueuedReminder {
+                    note: Note::from("first").with_id("1"),
+                    queued_at: 100
+                },
+                QueuedReminder {
+                    note: Note::from("second")
+                        .with_template("Todo", vec!["Ana".to_string()])
+                        .with_id("2"),
+                    queued_at: 200
+                }
+            ],
+            reopened.queue
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn queued_reminders_are_given_an_id() -> Result<(), OutboxError> {
+        let (_dir, path) = outbox_path();
+
+        let mut outbox = Outbox::open(&path)?;
+        outbox.push("first".into(), 100)?;
+        outbox.push("second".into(), 200)?;
+
+        let first = outbox.queue[0].note.id.as_ref();
+        assert!(first.is_some());
+        assert_ne!(first, outbox.queue[1].note.id.as_ref());
+        Ok(())
+    }
+
+    #[test]
+    fn reminders_queued_before_templates_still_open() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = outbox_path();
+        fs::write(&path, r#"[{"text": "first", "queued_at": 100}]"#)?;
+
+        let outbox = Outbox::open(&path)?;
+
+        assert_eq!(Note::from("first"), outbox.queue[0].note);
+        Ok(())
+    }
+
+    #[test]
+    fn a_corrupt_outbox_is_reported() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = outbox_path();
+        fs::write(&path, "not json")?;
+
+        let outbox = Outbox::open(&path);
+
+        assert!(matches!(outbox, Err(OutboxError::Corrupt(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn replay_saves_queued_reminders_in_order() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = outbox_path();
+        let storage = MockStorage::new().with_inbox("* Inbox").as_rc();
+        let mut inbox = Inbox::load(Rc::clone(&storage))?;
+
+        let mut outbox = Outbox::open(&path)?;
+        outbox.push(Note::from("first").with_id("1"), 100)?;
+        outbox.push(Note::from("second").with_id("2"), 200)?;
+        outbox.replay(&mut inbox)?;
+
+        assert_eq!(
+            "* Inbox\n** first\n   :PROPERTIES:\n   :ID:       1\n   :END:\n** second\n   :PROPERTIES:\n   :ID:       2\n   :END:",
+            storage.inbox()
+        );
+        assert_eq!(0, outbox.pending());
+        assert_eq!(0, Outbox::open(&path)?.pending());
+        Ok(())
+    }
+
+    #[test]
+    fn replay_keeps_reminders_storage_did_not_take() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = outbox_path();
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox")
+            .with_update_error(StorageError::Network("offline".to_string()))
+            .as_rc();
+        let mut inbox = Inbox::load(Rc::clone(&storage))?;
+
+        let mut outbox = Outbox::open(&path)?;
+        outbox.push("first".into(), 100)?;
+        outbox.push("second".into(), 200)?;
+        let result = outbox.replay(&mut inbox);
+
+        assert!(matches!(result, Err(OutboxError::CouldNotDeliver(_))));
+        assert_eq!(2, Outbox::open(&path)?.pending());
+        Ok(())
+    }
+
+    // The outbox can't be written after the save, like when the disk is full
+    #[test]
+    fn replay_never_saves_a_reminder_twice() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = outbox_path();
+        let storage = MockStorage::new().with_inbox("* Inbox").as_rc();
+        let mut inbox = Inbox::load(Rc::clone(&storage))?;
+
+        let mut outbox = Outbox::open(&path)?;
+        outbox.push(Note::from("first").with_id("1"), 100)?;
+        let blocked = path.with_extension("tmp");
+        fs::create_dir(&blocked)?;
+        let result = outbox.replay(&mut inbox);
+        fs::remove_dir(&blocked)?;
+        let mut reopened = Outbox::open(&path)?;
+        let pending = reopened.pending();
+        reopened.replay(&mut Inbox::load(Rc::clone(&storage))?)?;
+
+        assert!(matches!(result, Err(OutboxError::Unwritable(_))));
+        assert_eq!(1, pending);
+        assert_eq!(
+            "* Inbox\n** first\n   :PROPERTIES:\n   :ID:       1\n   :END:",
+            storage.inbox()
+        );
+        assert_eq!(vec![1], storage.writes());
+        assert_eq!(0, Outbox::open(&path)?.pending());
+        Ok(())
+    }
+
+    // Reminders that couldn't be saved wait here rather than in the inbox,
+    // and are put on top of whatever else was added in the meantime.
+    #[test]
+    fn replay_reapplies_reminders_after_a_conflict() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = outbox_path();
+        let storage = MockStorage::new()
+            .with_inbox("* Inbox")
+            .with_conflict("* Inbox\n** Added from emacs")
+            .as_rc();
+        let mut inbox = Inbox::load(Rc::clone(&storage))?;
+
+        let mut outbox = Outbox::open(&path)?;
+        outbox.push(Note::from("first todo").with_id("1"), 100)?;
+        outbox.push(Note::from("second todo").with_id("2"), 200)?;
+        outbox.replay(&mut inbox)?;
+
+        assert_eq!(
+            "* Inbox\n** Added from emacs\n** first todo\n   :PROPERTIES:\n   :ID:       1\n   :END:\n** second todo\n   :PROPERTIES:\n   :ID:       2\n   :END:",
+            storage.inbox()
+        );
+        assert_eq!(0, Outbox::open(&path)?.pending());
+        Ok(())
+    }
+
+    #[test]
+    fn batched_replay_saves_everything_queued_in_one_write(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = outbox_path();
+        let storage = MockStorage::new().with_inbox("* Inbox").as_rc();
+        let mut inbox = Inbox::load(Rc::clone(&storage))?;
+
+        let mut outbox = Outbox::open(&path)?;
+        outbox.push(Note::from("first").with_id("1"), 100)?;
+        outbox.push(Note::from("second").with_id("2"), 200)?;
+        outbox.replay_batched(&mut inbox)?;
+
+        assert_eq!(
+            "* Inbox\n** first\n   :PROPERTIES:\n   :ID:       1\n   :END:\n** second\n   :PROPERTIES:\n   :ID:       2\n   :END:",
+            storage.inbox()
+        );
+        assert_eq!(vec![2], storage.writes());
+        assert_eq!(0, Outbox::open(&path)?.pending());
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+use super::{FileChange, Storage, StorageError};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FileConfig {
+    pub path: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct FileStorage {
+    path: PathBuf,
+    loaded: RefCell<Option<String>>,
+}
+
+impl FileStorage {
+    pub fn new(path: PathBuf) -> Self {
+        FileStorage {
+            path,
+            loaded: RefCell::new(None),
+        }
+    }
+
+    fn sibling(&self, path: &str) -> Result<PathBuf, StorageError> {
+        let file_name = file_name(&self.path)?.to_string_lossy();
+        Ok(self.path.with_file_name(path.replace("%s", &file_name)))
+    }
+
+    fn write(&self, reminders: &str) -> Result<(), StorageError> {
+        replace_file(&self.path, reminders)?;
+        *self.loaded.borrow_mut() = Some(reminders.to_string());
+        Ok(())
+    }
+
+    fn ensure_unchanged(&self) -> Result<(), StorageError> {
+        if let Some(loaded) = self.loaded.borrow().as_ref() {
+            let on_disk = match fs::read_to_string(&self.path) {
+                Ok(contents) => Some(contents),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+                Err(err) => return Err(err.into()),
+            };
+            if on_disk.as_ref() != Some(loaded) {
+                return Err(StorageError::Conflict);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn file_name(path: &Path) -> Result<&OsStr, StorageError> {
+    path.file_name()
+        .ok_or_else(|| StorageError::Local(format!("{} is not a file path", path.display())))
+}
+
+fn temp_path(path: &Path) -> Result<PathBuf, StorageError> {
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name(path)?);
+    temp_name.push(".capture-tmp");
+    Ok(path.with_file_name(temp_name))
+}
+
+fn replace_file(path: &Path, contents: &str) -> Result<(), StorageError> {
+    let temp_path = temp_path(path)?;
+    if let Err(err) = write_atomically(&temp_path, path, contents) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+fn write_atomically(temp_path: &Path, path: &Path, contents: &str) -> io::Result<()> {
+    let mut temp_file = fs::File::create(temp_path)?;
+    temp_file.write_all(contents.as_bytes())?;
+    temp_file.sync_all()?;
+    fs::rename(temp_path, path)
+}
+
+impl Storage for FileStorage {
+    fn update(&self, reminders: &String) -> Result<(), StorageError> {
+        self.ensure_unchanged()?;
+        self.write(reminders)
+    }
+
+    fn create(&self, reminders: &String) -> Result<(), StorageError> {
+        if self.path.exists() {
+            return Err(StorageError::Conflict);
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.write(reminders)
+    }
+
+    fn load(&self) -> Result<String, StorageError> {
+        let contents = fs::read_to_string(&self.path)?;
+        *self.loaded.borrow_mut() = Some(contents.clone());
+        Ok(contents)
+    }
+
+    fn load_file(&self, path: &str) -> Result<String, StorageError> {
+        Ok(fs::read_to_string(self.sibling(path)?)?)
+    }
+
+    // Files can't be replaced together, so the other files go first. If the
+    // inbox can't be written after them its reminders are all still there.
+    fn update_with_files(
+        &self,
+        reminders: &String,
+        files: &[FileChange],
+        _message: &str,
+    ) -> Result<(), StorageError> {
+        self.ensure_unchanged()?;
+        for file in files {
+            let path = self.sibling(&file.path)?;
+            if file.created && path.exists() {
+                return Err(StorageError::Conflict);
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            replace_file(&path, &file.contents)?;
+        }
+        self.write(reminders)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn inbox_file(contents: &str) -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().expect("Could not create temp dir");
+        let path = dir.path().join("inbox.org");
+        fs::write(&path, contents).expect("Could not write inbox");
+        (dir, path)
+    }
+
+    #[test]
+    fn load_reads_the_file() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = inbox_file("* Inbox\n** one\n");
+        let storage = FileStorage::new(path);
+
+        assert_eq!("* Inbox\n** one\n", storage.load()?);
+        Ok(())
+    }
+
+    #[test]
+    fn load_reports_a_missing_file_as_not_found() {
+        let dir = tempfile::tempdir().expect("Could not create temp dir");
+        let storage = FileStorage::new(dir.path().join("missing.org"));
+
+        assert_eq!(Err(StorageError::NotFound), storage.load());
+    }
+
+    #[test]
+    fn update_replaces_the_file_and_leaves_no_temp_file() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let (dir, path) = inbox_file("* Inbox\n");
+        let storage = FileStorage::new(path.clone());
+
+        storage.load()?;
+        storage.update(&"* Inbox\n** one\n".to_string())?;
+
+        assert_eq!("* Inbox\n** one\n", fs::read_to_string(&path)?);
+        assert_eq!(1, fs::read_dir(dir.path())?.count());
+        Ok(())
+    }
+
+    #[test]
+    fn consecutive_updates_do_not_look_like_outside_changes(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = inbox_file("* Inbox\n");
+        let storage = FileStorage::new(path.clone());
+
+        storage.load()?;
+        storage.update(&"* Inbox\n** one\n".to_string())?;
+        storage.update(&"* Inbox\n** one\n** two\n".to_string())?;
+
+        assert_eq!("* Inbox\n** one\n** two\n", fs::read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn update_refuses_to_overwrite_a_file_changed_since_load(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = inbox_file("* Inbox\n");
+        let storage = FileStorage::new(path.clone());
+
+        storage.load()?;
+        fs::write(&path, "* Inbox\n** added in emacs\n")?;
+        let result = storage.update(&"* Inbox\n** one\n".to_string());
+
+        assert_eq!(Err(StorageError::Conflict), result);
+        assert_eq!("* Inbox\n** added in emacs\n", fs::read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn update_refuses_to_recreate_a_file_deleted_since_load(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = inbox_file("* Inbox\n");
+        let storage = FileStorage::new(path.clone());
+
+        storage.load()?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(
+            Err(StorageError::Conflict),
+            storage.update(&"* Inbox\n** one\n".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn create_makes_the_file_and_its_directories() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("gtd").join("inbox.org");
+        let storage = FileStorage::new(path.clone());
+
+        storage.create(&"* Inbox\n** one\n".to_string())?;
+
+        assert_eq!("* Inbox\n** one\n", fs::read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn create_refuses_to_overwrite_an_existing_file() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, path) = inbox_file("* Inbox\n** added in emacs\n");
+        let storage = FileStorage::new(path.clone());
+
+        let result = storage.create(&"* Inbox\n** one\n".to_string());
+
+        assert_eq!(Err(StorageError::Conflict), result);
+        assert_eq!("* Inbox\n** added in emacs\n", fs::read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn other_files_are_written_next_to_the_inbox() -> Result<(), Box<dyn std::error::Error>> {
+        let (dir, path) = inbox_file("* Inbox\n** one\n");
+        let storage = FileStorage::new(path.clone());
+
+        storage.load()?;
+        assert_eq!(Err(StorageError::NotFound), storage.load_file("%s_archive"));
+        storage.update_with_files(
+            &"* Inbox\n".to_string(),
+            &[FileChange {
+                path: "%s_archive".to_string(),
+                contents: "* one\n".to_string(),
+                created: true,
+            }],
+            "Reminder \"one\" archived from Capture app",
+        )?;
+
+        assert_eq!("* Inbox\n", fs::read_to_string(&path)?);
+        assert_eq!("* one\n", storage.load_file("%s_archive")?);
+        assert_eq!(
+            "* one\n",
+            fs::read_to_string(dir.path().join("inbox.org_archive"))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn an_other_file_created_since_it_was_read_is_a_conflict(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (dir, path) = inbox_file("* Inbox\n** one\n");
+        fs::write(dir.path().join("archive.org"), "* kept\n")?;
+        let storage = FileStorage::new(path.clone());
+
+        storage.load()?;
+        let result = storage.update_with_files(
+            &"* Inbox\n".to_string(),
+            &[FileChange {
+                path: "archive.org".to_string(),
+                contents: "* one\n".to_string(),
+                created: true,
+            }],
+            "",
+        );
+
+        assert_eq!(Err(StorageError::Conflict), result);
+        assert_eq!("* Inbox\n** one\n", fs::read_to_string(&path)?);
+        Ok(())
+    }
+}
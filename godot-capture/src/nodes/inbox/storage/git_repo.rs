@@ -0,0 +1,464 @@
+use super::{
+    batch_commit_message, sibling_path, FileChange, Storage, StorageError, ADDED_COMMIT_MESSAGE,
+};
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GitRepoError {
+    #[error("git {command} failed: {stderr}")]
+    CommandFailed { command: String, stderr: String },
+
+    #[error("Reminder committed locally but pushing to {remote} failed: {reason}")]
+    PushRejected { remote: String, reason: String },
+}
+
+// The commit is kept when a push fails, so that is only a problem reaching the
+// remote. Anything else git refuses to do is about the local repository.
+impl From<GitRepoError> for StorageError {
+    fn from(err: GitRepoError) -> Self {
+        match err {
+            GitRepoError::PushRejected { .. } => StorageError::Network(err.to_string()),
+            GitRepoError::CommandFailed { .. } => StorageError::Local(err.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GitRepoConfig {
+    pub repository: PathBuf,
+    pub file_path: String,
+    #[serde(default)]
+    pub remote: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct GitRepoStorage {
+    config: GitRepoConfig,
+}
+
+impl GitRepoStorage {
+    pub fn new(config: GitRepoConfig) -> Self {
+        GitRepoStorage { config }
+    }
+
+    fn inbox_path(&self) -> PathBuf {
+        self.config.repository.join(&self.config.file_path)
+    }
+
+    fn git(&self, args: &[&str]) -> std::io::Result<Output> {
+        Command::new("git")
+            .arg("-C")
+            .arg(&self.config.repository)
+            .args(args)
+            .output()
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String, GitRepoError> {
+        let output = self.git(args).map_err(|err| GitRepoError::CommandFailed {
+            command: args.join(" "),
+            stderr: err.to_string(),
+        })?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        } else {
+            Err(GitRepoError::CommandFailed {
+                command: args.join(" "),
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            })
+        }
+    }
+
+    fn has_staged_changes(&self, paths: &[&str]) -> Result<bool, StorageError> {
+        let args = [&["diff", "--cached", "--quiet", "--"], paths].concat();
+        let output = self.git(&args)?;
+        Ok(!output.status.success())
+    }
+
+    // Only the files we wrote are committed so anything else that is dirty in
+    // the working tree is left alone for the user to deal with.
+    fn commit(&self, message: &str, paths: &[&str]) -> Result<(), StorageError> {
+        self.run(&[&["add", "--"], paths].concat())?;
+        if self.has_staged_changes(paths)? {
+            self.run(&[&["commit", "-m", message, "--"], paths].concat())?;
+        }
+        Ok(())
+    }
+
+    fn write_and_commit(&self, reminders: &str, message: &str) -> Result<(), StorageError> {
+        self.write_files_and_commit(reminders, &[], message)
+    }
+
+    fn write_files_and_commit(
+        &self,
+        reminders: &str,
+        files: &[FileChange],
+        message: &str,
+    ) -> Result<(), StorageError> {
+        let mut paths = vec![self.config.file_path.clone()];
+        for file in files {
+            let path = sibling_path(&self.config.file_path, &file.path);
+            let full_path = self.config.repository.join(&path);
+            if file.created && full_path.exists() {
+                return Err(StorageError::Conflict);
+            }
+            if let Some(parent) = full_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(full_path, &file.contents)?;
+            paths.push(path);
+        }
+        // A new inbox can be in a folder that isn't in the repository yet.
+        if let Some(parent) = self.inbox_path().parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(self.inbox_path(), reminders)?;
+        self.commit(
+            message,
+            &paths.iter().map(String::as_str).collect::<Vec<&str>>(),
+        )?;
+
+        match &self.config.remote {
+            Some(remote) => Ok(self.push(remote)?),
+            None => Ok(()),
+        }
+    }
+
+    // A rejected push usually means the remote moved on, so rebase our commit
+    // on top of it once and try again. The commit always stays local.
+    fn push(&self, remote: &str) -> Result<(), GitRepoError> {
+        if self.run(&["push", remote, "HEAD"]).is_ok() {
+            return Ok(());
+        }
+
+        let branch = self.run(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+        let retried = self
+            .run(&["pull", "--rebase", "--autostash", remote, &branch])
+            .and_then(|_| self.run(&["push", remote, "HEAD"]));
+
+        retried.map(|_| ()).map_err(|err| {
+            let _ = self.git(&["rebase", "--abort"]);
+            GitRepoError::PushRejected {
+                remote: remote.to_string(),
+                reason: err.to_string(),
+            }
+        })
+    }
+}
+
+impl Storage for GitRepoStorage {
+    fn update(&self, reminders: &String) -> Result<(), StorageError> {
+        self.write_and_commit(reminders, ADDED_COMMIT_MESSAGE)
+    }
+
+    fn update_with_message(&self, reminders: &String, message: &str) -> Result<(), StorageError> {
+        self.write_and_commit(reminders, message)
+    }
+
+    fn update_batch(&self, reminders: &String, added: usize) -> Result<(), StorageError> {
+        self.write_and_commit(reminders, &batch_commit_message(added))
+    }
+
+    fn create(&self, reminders: &String) -> Result<(), StorageError> {
+        let inbox_path = self.inbox_path();
+        if inbox_path.exists() {
+            return Err(StorageError::Conflict);
+        }
+        if let Some(parent) = inbox_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.update(reminders)
+    }
+
+    fn load(&self) -> Result<String, StorageError> {
+        Ok(fs::read_to_string(self.inbox_path())?)
+    }
+
+    fn load_file(&self, path: &str) -> Result<String, StorageError> {
+        let path = sibling_path(&self.config.file_path, path);
+        Ok(fs::read_to_string(self.config.repository.join(path))?)
+    }
+
+    fn update_with_files(
+        &self,
+        reminders: &String,
+        files: &[FileChange],
+        message: &str,
+    ) -> Result<(), StorageError> {
+        self.write_files_and_commit(reminders, files, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .expect("git should run");
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    fn clone(remote: &Path, into: &Path) {
+        git(
+            remote.parent().unwrap(),
+            &[
+                "clone",
+                "-q",
+                remote.to_str().unwrap(),
+                into.to_str().unwrap(),
+            ],
+        );
+        git(into, &["config", "user.name", "Capture Tests"]);
+        git(into, &["config", "user.email", "capture@example.com"]);
+    }
+
+    // A bare "remote" with a clone holding an inbox file in its first commit
+    struct Repos {
+        _dir: TempDir,
+        remote: PathBuf,
+        clone: PathBuf,
+    }
+
+    impl Repos {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().expect("Could not create temp dir");
+            let remote = dir.path().join("remote.git");
+            let clone_path = dir.path().join("clone");
+            git(
+                dir.path(),
+                &["init", "-q", "--bare", remote.to_str().unwrap()],
+            );
+            clone(&remote, &clone_path);
+
+            fs::create_dir(clone_path.join("gtd")).unwrap();
+            fs::write(clone_path.join("gtd/inbox.org"), "* Inbox\n").unwrap();
+            git(&clone_path, &["add", "."]);
+            git(&clone_path, &["commit", "-q", "-m", "First"]);
+            git(&clone_path, &["push", "-q", "origin", "HEAD"]);
+
+            Repos {
+                _dir: dir,
+                remote,
+                clone: clone_path,
+            }
+        }
+
+        fn storage(&self, remote: Option<&str>) -> GitRepoStorage {
+            GitRepoStorage::new(GitRepoConfig {
+                repository: self.clone.clone(),
+                file_path: "gtd/inbox.org".to_string(),
+                remote: remote.map(String::from),
+            })
+        }
+
+        fn second_clone(&self) -> PathBuf {
+            let path = self.clone.with_file_name("second");
+            clone(&self.remote, &path);
+            path
+        }
+    }
+
+    #[test]
+    fn load_reads_the_inbox_from_the_working_tree() -> Result<(), StorageError> {
+        let repos = Repos::new();
+
+        assert_eq!("* Inbox\n", repos.storage(None).load()?);
+        Ok(())
+    }
+
+    #[test]
+    fn update_commits_the_inbox_file() -> Result<(), StorageError> {
+        let repos = Repos::new();
+
+        repos
+            .storage(None)
+            .update(&"* Inbox\n** note\n".to_string())?;
+
+        assert_eq!(
+            "Reminder(s) added from Capture app",
+            git(&repos.clone, &["log", "-1", "--format=%s"])
+        );
+        assert_eq!("", git(&repos.clone, &["status", "--porcelain"]));
+        Ok(())
+    }
+
+    #[test]
+    fn create_commits_a_new_inbox_file() -> Result<(), StorageError> {
+        let repos = Repos::new();
+        let storage = GitRepoStorage::new(GitRepoConfig {
+            repository: repos.clone.clone(),
+            file_path: "notes/inbox.org".to_string(),
+            remote: None,
+        });
+
+        assert_eq!(Err(StorageError::NotFound), storage.load());
+        storage.create(&"* Inbox\n** note\n".to_string())?;
+
+        assert_eq!(
+            "notes/inbox.org",
+            git(&repos.clone, &["show", "--name-only", "--format=", "HEAD"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn update_batch_says_how_many_reminders_were_added() -> Result<(), StorageError> {
+        let repos = Repos::new();
+
+        repos
+            .storage(None)
+            .update_batch(&"* Inbox\n** one\n** two\n".to_string(), 2)?;
+
+        assert_eq!(
+            "2 reminders added from Capture app",
+            git(&repos.clone, &["log", "-1", "--format=%s"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn changes_are_committed_with_their_message() -> Result<(), StorageError> {
+        let repos = Repos::new();
+
+        repos
+            .storage(None)
+            .update_with_message(&"* Inbox\n** kept\n".to_string(), "Reminder deleted")?;
+
+        assert_eq!(
+            "Reminder deleted",
+            git(&repos.clone, &["log", "-1", "--format=%s"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn other_files_are_committed_with_the_inbox() -> Result<(), StorageError> {
+        let repos = Repos::new();
+        let storage = repos.storage(None);
+
+        assert_eq!(Err(StorageError::NotFound), storage.load_file("%s_archive"));
+        storage.update_with_files(
+            &"* Inbox\n** kept\n".to_string(),
+            &[FileChange {
+                path: "%s_archive".to_string(),
+                contents: "* note\n".to_string(),
+                created: true,
+            }],
+            "Reminder \"note\" archived from Capture app",
+        )?;
+
+        assert_eq!("* note\n", storage.load_file("%s_archive")?);
+        assert_eq!(
+            "gtd/inbox.org\ngtd/inbox.org_archive",
+            git(&repos.clone, &["show", "--name-only", "--format=", "HEAD"])
+        );
+        assert_eq!(
+            "Reminder \"note\" archived from Capture app",
+            git(&repos.clone, &["log", "-1", "--format=%s"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn update_without_changes_does_not_create_an_empty_commit() -> Result<(), StorageError> {
+        let repos = Repos::new();
+
+        repos.storage(None).update(&"* Inbox\n".to_string())?;
+
+        assert_eq!("First", git(&repos.clone, &["log", "-1", "--format=%s"]));
+        Ok(())
+    }
+
+    #[test]
+    fn update_leaves_other_dirty_files_alone() -> Result<(), StorageError> {
+        let repos = Repos::new();
+        fs::write(repos.clone.join("gtd/projects.org"), "* Projects\n")?;
+        fs::write(repos.clone.join("notes.org"), "staged")?;
+        git(&repos.clone, &["add", "notes.org"]);
+
+        repos
+            .storage(None)
+            .update(&"* Inbox\n** note\n".to_string())?;
+
+        assert_eq!(
+            "gtd/inbox.org",
+            git(&repos.clone, &["show", "--name-only", "--format=", "HEAD"])
+        );
+        assert_eq!(
+            "A  notes.org\n?? gtd/projects.org",
+            git(&repos.clone, &["status", "--porcelain"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn update_pushes_to_the_configured_remote() -> Result<(), StorageError> {
+        let repos = Repos::new();
+
+        repos
+            .storage(Some("origin"))
+            .update(&"* Inbox\n** note\n".to_string())?;
+
+        assert_eq!(
+            "Reminder(s) added from Capture app",
+            git(&repos.remote, &["log", "-1", "--format=%s"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn update_rebases_when_the_remote_has_moved_on() -> Result<(), StorageError> {
+        let repos = Repos::new();
+        let second = repos.second_clone();
+        fs::write(second.join("README.org"), "Elsewhere")?;
+        git(&second, &["add", "."]);
+        git(&second, &["commit", "-q", "-m", "Elsewhere"]);
+        git(&second, &["push", "-q", "origin", "HEAD"]);
+
+        repos
+            .storage(Some("origin"))
+            .update(&"* Inbox\n** note\n".to_string())?;
+
+        assert_eq!(
+            "Reminder(s) added from Capture app\nElsewhere\nFirst",
+            git(&repos.remote, &["log", "--format=%s"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn update_keeps_the_local_commit_when_the_push_cannot_be_rebased() {
+        let repos = Repos::new();
+        let second = repos.second_clone();
+        fs::write(second.join("gtd/inbox.org"), "* Inbox\n** from elsewhere\n").unwrap();
+        git(&second, &["commit", "-q", "-am", "Elsewhere"]);
+        git(&second, &["push", "-q", "origin", "HEAD"]);
+
+        let result = repos
+            .storage(Some("origin"))
+            .update(&"* Inbox\n** note\n".to_string());
+
+        assert!(matches!(result, Err(StorageError::Network(_))));
+        assert_eq!(
+            "Reminder(s) added from Capture app",
+            git(&repos.clone, &["log", "-1", "--format=%s"])
+        );
+        assert_eq!("", git(&repos.clone, &["status", "--porcelain"]));
+    }
+}
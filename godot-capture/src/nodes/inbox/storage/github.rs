@@ -0,0 +1,420 @@
+use super::{
+    check_response, encode_path, read_json, sibling_path, FileChange, Storage, StorageError,
+    ADDED_COMMIT_MESSAGE,
+};
+use crate::nodes::inbox::decoder::decode_content;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use ureq::json;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GithubConfig {
+    #[serde(default = "default_github_url")]
+    pub api_url: String,
+    pub owner: String,
+    pub repo: String,
+    pub file_path: String,
+    #[serde(default = "default_github_branch")]
+    pub branch: String,
+    pub token: String,
+}
+
+fn default_github_url() -> String {
+    "https://api.github.com".to_string()
+}
+
+fn default_github_branch() -> String {
+    "main".to_string()
+}
+
+#[derive(Debug)]
+pub struct GithubStorage {
+    config: GithubConfig,
+    sha: RefCell<Option<String>>,
+    file_shas: RefCell<HashMap<String, String>>,
+}
+
+impl GithubStorage {
+    pub fn new(config: GithubConfig) -> Self {
+        GithubStorage {
+            config,
+            sha: RefCell::new(None),
+            file_shas: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn contents_url(&self) -> String {
+        self.contents_url_for(&self.config.file_path)
+    }
+
+    fn contents_url_for(&self, file_path: &str) -> String {
+        format!(
+            "{}/repos/{}/{}/contents/{}",
+            self.config.api_url.trim_end_matches('/'),
+            self.config.owner,
+            self.config.repo,
+            encode_path(file_path)
+        )
+    }
+
+    fn branch_url(&self) -> String {
+        format!(
+            "{}/repos/{}/{}/branches/{}",
+            self.config.api_url.trim_end_matches('/'),
+            self.config.owner,
+            self.config.repo,
+            encode_path(&self.config.branch)
+        )
+    }
+
+    fn request(&self, method: &str, file_path: &str) -> ureq::Request {
+        self.request_to(method, &self.contents_url_for(file_path))
+    }
+
+    fn request_to(&self, method: &str, url: &str) -> ureq::Request {
+        let mut request = ureq::request(method, url);
+        request
+            .set("Authorization", &format!("token {}", self.config.token))
+            .set("Accept", "application/vnd.github.v3+json")
+            .set("User-Agent", "Capture");
+        request
+    }
+}
+
+impl GithubStorage {
+    fn put(
+        &self,
+        reminders: &String,
+        sha: Option<&String>,
+        message: &str,
+    ) -> Result<(), StorageError> {
+        let sha = self.put_file(&self.config.file_path, reminders, sha, message)?;
+        *self.sha.borrow_mut() = sha;
+        Ok(())
+    }
+
+    // Without a sha GitHub creates the file, and answers 422 if it exists
+    fn put_file(
+        &self,
+        file_path: &str,
+        contents: &str,
+        sha: Option<&String>,
+        message: &str,
+    ) -> Result<Option<String>, StorageError> {
+        let mut content = json!({
+            "branch": self.config.branch,
+            "content": base64::encode(contents),
+            "message": message
+        });
+        if let Some(sha) = sha {
+            content["sha"] = json!(sha);
+        }
+        let response = self.request("PUT", file_path).send_json(content);
+
+        if sha.is_none() && response.status() == 422 {
+            return Err(StorageError::Conflict);
+        }
+        let response = read_json(check_response(response)?)?;
+        Ok(response["content"]["sha"].as_str().map(String::from))
+    }
+
+    fn get_file(&self, file_path: &str) -> Result<ureq::SerdeValue, StorageError> {
+        let mut request = self.request("GET", file_path);
+        let resp = request.query("ref", &self.config.branch).call();
+        read_json(check_response(resp)?)
+    }
+
+    // GitHub answers 404 for a private repository or a branch that isn't
+    // there too, which mustn't read as a missing inbox.
+    fn missing_file(&self) -> StorageError {
+        match check_response(self.request_to("GET", &self.branch_url()).call()) {
+            Ok(_) => StorageError::NotFound,
+            Err(StorageError::NotFound) => StorageError::MissingRepository(format!(
+                "{}/{} {}",
+                self.config.owner, self.config.repo, self.config.branch
+            )),
+            Err(err) => err,
+        }
+    }
+}
+
+impl Storage for GithubStorage {
+    fn update(&self, reminders: &String) -> Result<(), StorageError> {
+        let sha = self.sha.borrow().clone();
+        self.put(reminders, sha.as_ref(), ADDED_COMMIT_MESSAGE)
+    }
+
+    fn update_with_message(&self, reminders: &String, message: &str) -> Result<(), StorageError> {
+        let sha = self.sha.borrow().clone();
+        self.put(reminders, sha.as_ref(), message)
+    }
+
+    fn create(&self, reminders: &String) -> Result<(), StorageError> {
+        self.put(reminders, None, ADDED_COMMIT_MESSAGE)
+    }
+
+    fn load(&self) -> Result<String, StorageError> {
+        let response = match self.get_file(&self.config.file_path) {
+            Err(StorageError::NotFound) => return Err(self.missing_file()),
+            response => response?,
+        };
+        *self.sha.borrow_mut() = response["sha"].as_str().map(String::from);
+        Ok(decode_content(response)?)
+    }
+
+    fn load_file(&self, path: &str) -> Result<String, StorageError> {
+        let file_path = sibling_path(&self.config.file_path, path);
+        let response = self.get_file(&file_path)?;
+        if let Some(sha) = response["sha"].as_str() {
+            self.file_shas
+                .borrow_mut()
+                .insert(file_path, sha.to_string());
+        }
+        Ok(decode_content(response)?)
+    }
+
+    // The contents API changes one file per commit, so the other files are
+    // committed first. If the inbox commit then fails its reminders are all
+    // still there.
+    fn update_with_files(
+        &self,
+        reminders: &String,
+        files: &[FileChange],
+        message: &str,
+    ) -> Result<(), StorageError> {
+        for file in files {
+            let file_path = sibling_path(&self.config.file_path, &file.path);
+            let sha = if file.created {
+                None
+            } else {
+                self.file_shas.borrow().get(&file_path).cloned()
+            };
+            match self.put_file(&file_path, &file.contents, sha.as_ref(), message)? {
+                Some(sha) => self.file_shas.borrow_mut().insert(file_path, sha),
+                None => self.file_shas.borrow_mut().remove(&file_path),
+            };
+        }
+        self.update_with_message(reminders, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::storage::tests::{StubResponse, StubServer};
+
+    fn github_config(api_url: &str) -> GithubConfig {
+        GithubConfig {
+            api_url: api_url.to_string(),
+            owner: "paytonrules".to_string(),
+            repo: "gtd".to_string(),
+            file_path: "gtd/inbox.org".to_string(),
+            branch: "main".to_string(),
+            token: "token".to_string(),
+        }
+    }
+
+    fn contents_response(content: &str, sha: &str) -> String {
+        json!({
+            "content": base64::encode(content),
+            "encoding": "base64",
+            "sha": sha
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn contents_url_keeps_directories_in_the_file_path() {
+        let storage = GithubStorage::new(github_config("https://api.github.com/"));
+
+        assert_eq!(
+            "https://api.github.com/repos/paytonrules/gtd/contents/gtd/inbox.org",
+            storage.contents_url()
+        );
+    }
+
+    #[test]
+    fn load_decodes_the_file_contents_from_the_branch() -> Result<(), StorageError> {
+        let server = StubServer::start(vec![(200, contents_response("* Inbox", "abc123"))]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        let inbox = storage.load()?;
+
+        assert_eq!("* Inbox", inbox);
+        let request = &server.requests()[0];
+        assert_eq!("GET", request.method);
+        assert_eq!(
+            "/repos/paytonrules/gtd/contents/gtd/inbox.org?ref=main",
+            request.path
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn load_reports_a_missing_file_as_not_found() {
+        let server = StubServer::start(vec![
+            (404, r#"{"message": "Not Found"}"#.to_string()),
+            (200, r#"{"name": "main"}"#.to_string()),
+        ]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        assert_eq!(Err(StorageError::NotFound), storage.load());
+        assert_eq!(
+            "/repos/paytonrules/gtd/branches/main",
+            server.requests()[1].path
+        );
+    }
+
+    #[test]
+    fn a_repository_that_cannot_be_seen_is_not_a_missing_file() {
+        let server = StubServer::start(vec![
+            (404, r#"{"message": "Not Found"}"#.to_string()),
+            (404, r#"{"message": "Not Found"}"#.to_string()),
+        ]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        assert_eq!(
+            Err(StorageError::MissingRepository(
+                "paytonrules/gtd main".to_string()
+            )),
+            storage.load()
+        );
+    }
+
+    #[test]
+    fn an_exhausted_rate_limit_is_not_an_authorization_failure() {
+        let server = StubServer::start_with(vec![StubResponse::new(
+            403,
+            r#"{"message": "API rate limit exceeded"}"#,
+        )
+        .with_header("X-RateLimit-Remaining", "0")]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        assert_eq!(Err(StorageError::RateLimited), storage.load());
+    }
+
+    #[test]
+    fn a_bad_token_is_unauthorized() {
+        let server =
+            StubServer::start(vec![(401, r#"{"message": "Bad credentials"}"#.to_string())]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        assert_eq!(Err(StorageError::Unauthorized), storage.load());
+    }
+
+    #[test]
+    fn update_sends_the_blob_sha_from_load() -> Result<(), Box<dyn std::error::Error>> {
+        let server = StubServer::start(vec![
+            (200, contents_response("* Inbox", "abc123")),
+            (200, r#"{"content": {"sha": "def456"}}"#.to_string()),
+        ]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        storage.load()?;
+        storage.update(&"* Inbox\n** note".to_string())?;
+
+        let request = &server.requests()[1];
+        let body: ureq::SerdeValue = serde_json::from_str(&request.body)?;
+        assert_eq!("PUT", request.method);
+        assert_eq!("abc123", body["sha"]);
+        assert_eq!("main", body["branch"]);
+        assert_eq!(base64::encode("* Inbox\n** note"), body["content"]);
+        Ok(())
+    }
+
+    #[test]
+    fn update_uses_the_new_sha_for_the_next_update() -> Result<(), Box<dyn std::error::Error>> {
+        let server = StubServer::start(vec![
+            (200, r#"{"content": {"sha": "def456"}}"#.to_string()),
+            (200, r#"{"content": {"sha": "ghi789"}}"#.to_string()),
+        ]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        storage.update(&"* Inbox\n** one".to_string())?;
+        storage.update(&"* Inbox\n** one\n** two".to_string())?;
+
+        let body: ureq::SerdeValue = serde_json::from_str(&server.requests()[1].body)?;
+        assert_eq!("def456", body["sha"]);
+        Ok(())
+    }
+
+    #[test]
+    fn update_fails_when_github_rejects_the_sha() {
+        let server = StubServer::start(vec![(409, r#"{"message": "conflict"}"#.to_string())]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        let result = storage.update(&"* Inbox".to_string());
+
+        assert_eq!(Err(StorageError::Conflict), result);
+    }
+
+    #[test]
+    fn create_puts_the_file_without_a_sha() -> Result<(), Box<dyn std::error::Error>> {
+        let server =
+            StubServer::start(vec![(201, r#"{"content": {"sha": "abc123"}}"#.to_string())]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        storage.create(&"* Inbox\n** note".to_string())?;
+
+        let body: ureq::SerdeValue = serde_json::from_str(&server.requests()[0].body)?;
+        assert_eq!(ureq::SerdeValue::Null, body["sha"]);
+        Ok(())
+    }
+
+    #[test]
+    fn creating_a_file_that_already_exists_is_a_conflict() {
+        let server = StubServer::start(vec![(
+            422,
+            r#"{"message": "Invalid request. \"sha\" wasn't supplied."}"#.to_string(),
+        )]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        assert_eq!(
+            Err(StorageError::Conflict),
+            storage.create(&"* Inbox".to_string())
+        );
+    }
+
+    #[test]
+    fn other_files_are_committed_before_the_inbox() -> Result<(), Box<dyn std::error::Error>> {
+        let server = StubServer::start(vec![
+            (200, contents_response("* Inbox\n** one", "abc123")),
+            (200, contents_response("* old", "def456")),
+            (200, r#"{"content": {"sha": "ghi789"}}"#.to_string()),
+            (200, r#"{"content": {"sha": "jkl012"}}"#.to_string()),
+        ]);
+        let storage = GithubStorage::new(github_config(&server.url()));
+
+        storage.load()?;
+        assert_eq!("* old", storage.load_file("%s_archive")?);
+        storage.update_with_files(
+            &"* Inbox".to_string(),
+            &[FileChange {
+                path: "%s_archive".to_string(),
+                contents: "* old\n* one".to_string(),
+                created: false,
+            }],
+            "Reminder \"one\" archived from Capture app",
+        )?;
+
+        let requests = server.requests();
+        let archive: ureq::SerdeValue = serde_json::from_str(&requests[2].body)?;
+        assert_eq!(
+            "/repos/paytonrules/gtd/contents/gtd/inbox.org_archive",
+            requests[2].path
+        );
+        assert_eq!("def456", archive["sha"]);
+        let inbox: ureq::SerdeValue = serde_json::from_str(&requests[3].body)?;
+        assert_eq!(
+            "/repos/paytonrules/gtd/contents/gtd/inbox.org",
+            requests[3].path
+        );
+        assert_eq!("abc123", inbox["sha"]);
+        assert_eq!(
+            "Reminder \"one\" archived from Capture app",
+            inbox["message"]
+        );
+        Ok(())
+    }
+}
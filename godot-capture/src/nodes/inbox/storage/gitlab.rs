@@ -0,0 +1,692 @@
+use super::{
+    batch_commit_message, check_response, default_branch, encode_path_segment, read_json,
+    sibling_path, FileChange, Storage, StorageError, ADDED_COMMIT_MESSAGE,
+};
+use crate::nodes::inbox::decoder::decode_content;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use ureq::json;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Project {
+    Id(u64),
+    Path(String),
+}
+
+impl Project {
+    fn encoded(&self) -> String {
+        match self {
+            Project::Id(id) => id.to_string(),
+            Project::Path(path) => encode_path_segment(path),
+        }
+    }
+}
+
+impl fmt::Display for Project {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Project::Id(id) => write!(f, "{}", id),
+            Project::Path(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GitlabConfig {
+    #[serde(default = "default_gitlab_url")]
+    pub base_url: String,
+    pub project: Project,
+    pub file_path: String,
+    #[serde(default = "default_branch")]
+    pub branch: String,
+}
+
+fn default_gitlab_url() -> String {
+    "https://gitlab.com".to_string()
+}
+
+#[derive(Debug)]
+pub struct GitlabStorage {
+    pub token: String,
+    config: GitlabConfig,
+    last_commit_id: RefCell<Option<String>>,
+    file_commit_ids: RefCell<HashMap<String, String>>,
+}
+
+impl GitlabStorage {
+    pub fn new(token: String, config: GitlabConfig) -> Self {
+        GitlabStorage {
+            token,
+            config,
+            last_commit_id: RefCell::new(None),
+            file_commit_ids: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn file_url(&self) -> String {
+        self.file_url_for(&self.config.file_path)
+    }
+
+    fn file_url_for(&self, file_path: &str) -> String {
+        format!(
+            "{}/api/v4/projects/{}/repository/files/{}",
+            self.config.base_url.trim_end_matches('/'),
+            self.config.project.encoded(),
+            encode_path_segment(file_path)
+        )
+    }
+
+    fn commits_url(&self) -> String {
+        format!(
+            "{}/api/v4/projects/{}/repository/commits",
+            self.config.base_url.trim_end_matches('/'),
+            self.config.project.encoded()
+        )
+    }
+
+    fn branch_url(&self) -> String {
+        format!(
+            "{}/api/v4/projects/{}/repository/branches/{}",
+            self.config.base_url.trim_end_matches('/'),
+            self.config.project.encoded(),
+            encode_path_segment(&self.config.branch)
+        )
+    }
+
+    fn authorization(&self) -> String {
+        format!("Bearer {}", self.token)
+    }
+
+    // Inbox writes go through the Commits API, which answers with the new
+    // commit. Its ID is what the next write is checked against, so a commit
+    // someone else makes after this one is always a conflict.
+    fn commit_inbox(
+        &self,
+        action: &str,
+        reminders: &String,
+        message: &str,
+        conflict_messages: &[&str],
+    ) -> Result<(), StorageError> {
+        let mut inbox = json!({
+            "action": action,
+            "file_path": self.config.file_path,
+            "content": reminders
+        });
+        if let Some(last_commit_id) = self.last_commit_id.borrow().as_ref() {
+            inbox["last_commit_id"] = json!(last_commit_id);
+        }
+        let response = ureq::post(&self.commits_url())
+            .set("Authorization", &self.authorization())
+            .send_json(json!({
+                "branch": self.config.branch,
+                "commit_message": message,
+                "actions": [inbox]
+            }));
+
+        let commit = read_json(check_gitlab_response(response, conflict_messages)?)?;
+        *self.last_commit_id.borrow_mut() = commit["id"].as_str().map(String::from);
+        Ok(())
+    }
+}
+
+impl GitlabStorage {
+    // A wrong project or branch is a 404 from the files API too, which
+    // mustn't read as a missing inbox.
+    fn missing_file(&self) -> StorageError {
+        let response = ureq::get(&self.branch_url())
+            .set("Authorization", &self.authorization())
+            .call();
+        match check_response(response) {
+            Ok(_) => StorageError::NotFound,
+            Err(StorageError::NotFound) => StorageError::MissingRepository(format!(
+                "{} {}",
+                self.config.project, self.config.branch
+            )),
+            Err(err) => err,
+        }
+    }
+}
+
+impl Storage for GitlabStorage {
+    fn update(&self, reminders: &String) -> Result<(), StorageError> {
+        self.commit_inbox(
+            "update",
+            reminders,
+            ADDED_COMMIT_MESSAGE,
+            &["changed since"],
+        )
+    }
+
+    fn update_with_message(&self, reminders: &String, message: &str) -> Result<(), StorageError> {
+        self.commit_inbox("update", reminders, message, &["changed since"])
+    }
+
+    // A single commit however many reminders were added
+    fn update_batch(&self, reminders: &String, added: usize) -> Result<(), StorageError> {
+        self.commit_inbox(
+            "update",
+            reminders,
+            &batch_commit_message(added),
+            &["changed since"],
+        )
+    }
+
+    fn create(&self, reminders: &String) -> Result<(), StorageError> {
+        self.commit_inbox(
+            "create",
+            reminders,
+            ADDED_COMMIT_MESSAGE,
+            &["already exists"],
+        )
+    }
+
+    fn load(&self) -> Result<String, StorageError> {
+        let resp = ureq::get(&self.file_url())
+            .query("ref", &self.config.branch)
+            .set("Authorization", &self.authorization())
+            .call();
+
+        let response = match check_response(resp) {
+            Err(StorageError::NotFound) => return Err(self.missing_file()),
+            response => read_json(response?)?,
+        };
+        *self.last_commit_id.borrow_mut() = response["last_commit_id"].as_str().map(String::from);
+        Ok(decode_content(response)?)
+    }
+
+    fn load_file(&self, path: &str) -> Result<String, StorageError> {
+        let file_path = sibling_path(&self.config.file_path, path);
+        let resp = ureq::get(&self.file_url_for(&file_path))
+            .query("ref", &self.config.branch)
+            .set("Authorization", &self.authorization())
+            .call();
+
+        let response = read_json(check_response(resp)?)?;
+        if let Some(last_commit_id) = response["last_commit_id"].as_str() {
+            self.file_commit_ids
+                .borrow_mut()
+                .insert(file_path, last_commit_id.to_string());
+        }
+        Ok(decode_content(response)?)
+    }
+
+    // Every file is an action in one commit, each checked against the commit
+    // it was read at.
+    fn update_with_files(
+        &self,
+        reminders: &String,
+        files: &[FileChange],
+        message: &str,
+    ) -> Result<(), StorageError> {
+        let mut actions = Vec::new();
+        for file in files {
+            let file_path = sibling_path(&self.config.file_path, &file.path);
+            let mut action = json!({
+                "action": if file.created { "create" } else { "update" },
+                "file_path": file_path,
+                "content": file.contents
+            });
+            if let Some(last_commit_id) = self.file_commit_ids.borrow().get(&file_path) {
+                action["last_commit_id"] = json!(last_commit_id);
+            }
+            actions.push(action);
+        }
+        let mut inbox = json!({
+            "action": "update",
+            "file_path": self.config.file_path,
+            "content": reminders
+        });
+        if let Some(last_commit_id) = self.last_commit_id.borrow().as_ref() {
+            inbox["last_commit_id"] = json!(last_commit_id);
+        }
+        actions.push(inbox);
+
+        let response = ureq::post(&self.commits_url())
+            .set("Authorization", &self.authorization())
+            .send_json(json!({
+                "branch": self.config.branch,
+                "commit_message": message,
+                "actions": actions
+            }));
+
+        let commit = read_json(check_gitlab_response(
+            response,
+            &["changed since", "already exists"],
+        )?)?;
+        let commit_id = commit["id"].as_str().map(String::from);
+        for file in files {
+            let file_path = sibling_path(&self.config.file_path, &file.path);
+            match &commit_id {
+                Some(commit_id) => self
+                    .file_commit_ids
+                    .borrow_mut()
+                    .insert(file_path, commit_id.clone()),
+                None => self.file_commit_ids.borrow_mut().remove(&file_path),
+            };
+        }
+        *self.last_commit_id.borrow_mut() = commit_id;
+        Ok(())
+    }
+}
+
+// GitLab reports writes that would lose someone else's changes as a bad
+// request, with a message saying why. ureq reports not reaching the server
+// as a 400 too, which is left to `check_response`.
+fn check_gitlab_response(
+    response: ureq::Response,
+    conflict_messages: &[&str],
+) -> Result<ureq::Response, StorageError> {
+    if response.status() == 400 && response.synthetic_error().is_none() {
+        let message = response.into_string().unwrap_or_default();
+        if conflict_messages
+            .iter()
+            .any(|conflict| message.contains(conflict))
+        {
+            return Err(StorageError::Conflict);
+        }
+        return Err(StorageError::InvalidResponse(format!(
+            "status 400 {}",
+            message
+        )));
+    }
+    check_response(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::storage::tests::{RecordedRequest, StubResponse, StubServer};
+
+    fn gitlab_config(project: Project, file_path: &str) -> GitlabConfig {
+        GitlabConfig {
+            base_url: default_gitlab_url(),
+            project,
+            file_path: file_path.to_string(),
+            branch: default_branch(),
+        }
+    }
+
+    #[test]
+    fn file_url_uses_project_id_and_encoded_file_path() {
+        let storage = GitlabStorage::new(
+            "token".to_string(),
+            gitlab_config(Project::Id(3723174), "gtd/inbox.org"),
+        );
+
+        assert_eq!(
+            "https://gitlab.com/api/v4/projects/3723174/repository/files/gtd%2Finbox%2Eorg",
+            storage.file_url()
+        );
+    }
+
+    #[test]
+    fn file_url_encodes_namespaced_project_paths() {
+        let storage = GitlabStorage::new(
+            "token".to_string(),
+            gitlab_config(Project::Path("paytonrules/gtd".to_string()), "inbox.org"),
+        );
+
+        assert_eq!(
+            "https://gitlab.com/api/v4/projects/paytonrules%2Fgtd/repository/files/inbox%2Eorg",
+            storage.file_url()
+        );
+    }
+
+    #[test]
+    fn file_url_supports_self_hosted_instances() {
+        let mut config = gitlab_config(Project::Id(12), "my notes/inbox.org");
+        config.base_url = "https://git.example.com/".to_string();
+        let storage = GitlabStorage::new("token".to_string(), config);
+
+        assert_eq!(
+            "https://git.example.com/api/v4/projects/12/repository/files/my%20notes%2Finbox%2Eorg",
+            storage.file_url()
+        );
+    }
+
+    #[test]
+    fn config_defaults_to_gitlab_com_and_master() -> Result<(), serde_json::Error> {
+        let config: GitlabConfig =
+            serde_json::from_str(r#"{"project": 3723174, "file_path": "gtd/inbox.org"}"#)?;
+
+        assert_eq!(gitlab_config(Project::Id(3723174), "gtd/inbox.org"), config);
+        Ok(())
+    }
+
+    fn stub_config(server: &StubServer) -> GitlabConfig {
+        let mut config = gitlab_config(Project::Id(12), "inbox.org");
+        config.base_url = server.url();
+        config
+    }
+
+    fn file_response(content: &str, last_commit_id: &str) -> StubResponse {
+        let body = json!({
+            "content": base64::encode(content),
+            "last_commit_id": last_commit_id
+        });
+        StubResponse::new(200, &body.to_string())
+    }
+
+    fn commit_response(id: &str) -> StubResponse {
+        StubResponse::new(201, &json!({ "id": id }).to_string())
+    }
+
+    fn sent_action(request: &RecordedRequest) -> ureq::SerdeValue {
+        let body: ureq::SerdeValue = serde_json::from_str(&request.body).unwrap();
+        body["actions"][0].clone()
+    }
+
+    #[test]
+    fn update_sends_the_last_commit_id_from_load() -> Result<(), StorageError> {
+        let server = StubServer::start_with(vec![
+            file_response("* Inbox", "abc123"),
+            commit_response("def456"),
+        ]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        assert_eq!("* Inbox", storage.load()?);
+        storage.update(&"* Inbox\n** note".to_string())?;
+
+        let requests = server.requests();
+        assert_eq!("POST", requests[1].method);
+        assert_eq!("/api/v4/projects/12/repository/commits", requests[1].path);
+        assert_eq!(
+            json!({
+                "action": "update",
+                "file_path": "inbox.org",
+                "content": "* Inbox\n** note",
+                "last_commit_id": "abc123"
+            }),
+            sent_action(&requests[1])
+        );
+        Ok(())
+    }
+
+    // Asking for the commit after writing could get someone else's, and
+    // their change would then be overwritten without a conflict.
+    #[test]
+    fn update_sends_the_commit_id_of_its_own_last_update() -> Result<(), StorageError> {
+        let server =
+            StubServer::start_with(vec![commit_response("def456"), commit_response("ghi789")]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        storage.update(&"* Inbox\n** one".to_string())?;
+        storage.update(&"* Inbox\n** one\n** two".to_string())?;
+
+        let requests = server.requests();
+        assert_eq!(2, requests.len());
+        assert_eq!(
+            ureq::SerdeValue::Null,
+            sent_action(&requests[0])["last_commit_id"]
+        );
+        assert_eq!(json!("def456"), sent_action(&requests[1])["last_commit_id"]);
+        Ok(())
+    }
+
+    #[test]
+    fn stale_update_is_a_conflict() {
+        let server = StubServer::start_with(vec![StubResponse::new(
+            400,
+            r#"{"message": "You are attempting to update a file that has changed since you started editing it."}"#,
+        )]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        let result = storage.update(&"* Inbox".to_string());
+
+        assert_eq!(Err(StorageError::Conflict), result);
+    }
+
+    #[test]
+    fn other_rejected_updates_are_not_conflicts() {
+        let server = StubServer::start_with(vec![StubResponse::new(
+            400,
+            r#"{"message": "branch is missing"}"#,
+        )]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        let result = storage.update(&"* Inbox".to_string());
+
+        assert!(matches!(result, Err(StorageError::InvalidResponse(_))));
+    }
+
+    #[test]
+    fn writes_that_never_reach_gitlab_are_network_errors() {
+        let mut config = gitlab_config(Project::Id(12), "inbox.org");
+        config.base_url = "not a url".to_string();
+        let storage = GitlabStorage::new("token".to_string(), config);
+        let inbox = "* Inbox".to_string();
+
+        assert!(matches!(
+            storage.update(&inbox),
+            Err(StorageError::Network(_))
+        ));
+        assert!(matches!(
+            storage.create(&inbox),
+            Err(StorageError::Network(_))
+        ));
+        assert!(matches!(
+            storage.update_batch(&inbox, 2),
+            Err(StorageError::Network(_))
+        ));
+        assert!(matches!(
+            storage.update_with_files(&inbox, &[], ""),
+            Err(StorageError::Network(_))
+        ));
+    }
+
+    #[test]
+    fn a_server_error_is_temporary() {
+        let server =
+            StubServer::start_with(vec![StubResponse::new(502, "<html>Bad Gateway</html>")]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        assert!(matches!(
+            storage.update(&"* Inbox".to_string()),
+            Err(StorageError::Network(_))
+        ));
+    }
+
+    #[test]
+    fn an_expired_token_is_unauthorized() {
+        let server = StubServer::start_with(vec![StubResponse::new(
+            401,
+            r#"{"message": "401 Unauthorized"}"#,
+        )]);
+        let storage = GitlabStorage::new("expired".to_string(), stub_config(&server));
+
+        assert_eq!(Err(StorageError::Unauthorized), storage.load());
+    }
+
+    #[test]
+    fn a_missing_file_is_not_found() {
+        let server = StubServer::start_with(vec![
+            StubResponse::new(404, r#"{"message": "404 File Not Found"}"#),
+            StubResponse::new(200, r#"{"name": "master"}"#),
+        ]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        assert_eq!(Err(StorageError::NotFound), storage.load());
+        assert_eq!(
+            "/api/v4/projects/12/repository/branches/master",
+            server.requests()[1].path
+        );
+    }
+
+    #[test]
+    fn a_missing_project_or_branch_is_not_a_missing_file() {
+        let server = StubServer::start_with(vec![
+            StubResponse::new(404, r#"{"message": "404 Project Not Found"}"#),
+            StubResponse::new(404, r#"{"message": "404 Project Not Found"}"#),
+        ]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        assert_eq!(
+            Err(StorageError::MissingRepository("12 master".to_string())),
+            storage.load()
+        );
+    }
+
+    #[test]
+    fn create_posts_the_new_file() -> Result<(), StorageError> {
+        let server =
+            StubServer::start_with(vec![commit_response("abc123"), commit_response("def456")]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        storage.create(&"* Inbox\n** note".to_string())?;
+        storage.update(&"* Inbox\n** note\n** another".to_string())?;
+
+        let requests = server.requests();
+        assert_eq!("POST", requests[0].method);
+        assert_eq!("/api/v4/projects/12/repository/commits", requests[0].path);
+        assert_eq!(
+            json!({
+                "action": "create",
+                "file_path": "inbox.org",
+                "content": "* Inbox\n** note"
+            }),
+            sent_action(&requests[0])
+        );
+        assert_eq!(json!("abc123"), sent_action(&requests[1])["last_commit_id"]);
+        Ok(())
+    }
+
+    #[test]
+    fn creating_a_file_that_already_exists_is_a_conflict() {
+        let server = StubServer::start_with(vec![StubResponse::new(
+            400,
+            r#"{"message": "A file with this name already exists"}"#,
+        )]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        assert_eq!(
+            Err(StorageError::Conflict),
+            storage.create(&"* Inbox".to_string())
+        );
+    }
+
+    #[test]
+    fn update_batch_writes_one_commit_through_the_commits_api(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let server = StubServer::start_with(vec![
+            file_response("* Inbox", "abc123"),
+            StubResponse::new(201, r#"{"id": "def456"}"#),
+            StubResponse::new(201, r#"{"id": "ghi789"}"#),
+        ]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        storage.load()?;
+        storage.update_batch(&"* Inbox\n** one\n** two\n** three".to_string(), 3)?;
+        storage.update_batch(&"* Inbox\n** one\n** two\n** three\n** four".to_string(), 1)?;
+
+        let requests = server.requests();
+        let body: ureq::SerdeValue = serde_json::from_str(&requests[1].body)?;
+        assert_eq!("POST", requests[1].method);
+        assert_eq!("/api/v4/projects/12/repository/commits", requests[1].path);
+        assert_eq!("3 reminders added from Capture app", body["commit_message"]);
+        assert_eq!(
+            json!([{
+                "action": "update",
+                "file_path": "inbox.org",
+                "content": "* Inbox\n** one\n** two\n** three",
+                "last_commit_id": "abc123"
+            }]),
+            body["actions"]
+        );
+        let next: ureq::SerdeValue = serde_json::from_str(&requests[2].body)?;
+        assert_eq!("def456", next["actions"][0]["last_commit_id"]);
+        Ok(())
+    }
+
+    #[test]
+    fn stale_batch_is_a_conflict() {
+        let server = StubServer::start_with(vec![StubResponse::new(
+            400,
+            r#"{"message": "The file has changed since you started editing it: inbox.org"}"#,
+        )]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        assert_eq!(
+            Err(StorageError::Conflict),
+            storage.update_batch(&"* Inbox".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn other_files_go_in_the_same_commit_as_the_inbox() -> Result<(), Box<dyn std::error::Error>> {
+        let server = StubServer::start_with(vec![
+            file_response("* Inbox\n** one", "abc123"),
+            file_response("* old", "def456"),
+            StubResponse::new(201, r#"{"id": "ghi789"}"#),
+        ]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        storage.load()?;
+        assert_eq!("* old", storage.load_file("%s_archive")?);
+        storage.update_with_files(
+            &"* Inbox".to_string(),
+            &[FileChange {
+                path: "%s_archive".to_string(),
+                contents: "* old\n* one".to_string(),
+                created: false,
+            }],
+            "Reminder \"one\" archived from Capture app",
+        )?;
+
+        let requests = server.requests();
+        assert_eq!(
+            "/api/v4/projects/12/repository/files/inbox%2Eorg%5Farchive?ref=master",
+            requests[1].path
+        );
+        let body: ureq::SerdeValue = serde_json::from_str(&requests[2].body)?;
+        assert_eq!("/api/v4/projects/12/repository/commits", requests[2].path);
+        assert_eq!(
+            "Reminder \"one\" archived from Capture app",
+            body["commit_message"]
+        );
+        assert_eq!(
+            json!([
+                {
+                    "action": "update",
+                    "file_path": "inbox.org_archive",
+                    "content": "* old\n* one",
+                    "last_commit_id": "def456"
+                },
+                {
+                    "action": "update",
+                    "file_path": "inbox.org",
+                    "content": "* Inbox",
+                    "last_commit_id": "abc123"
+                }
+            ]),
+            body["actions"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn a_new_other_file_is_created_in_the_commit() -> Result<(), Box<dyn std::error::Error>> {
+        let server = StubServer::start_with(vec![StubResponse::new(
+            400,
+            r#"{"message": "A file with this name already exists"}"#,
+        )]);
+        let storage = GitlabStorage::new("token".to_string(), stub_config(&server));
+
+        let result = storage.update_with_files(
+            &"* Inbox".to_string(),
+            &[FileChange {
+                path: "archive.org".to_string(),
+                contents: "* one".to_string(),
+                created: true,
+            }],
+            "",
+        );
+
+        let body: ureq::SerdeValue = serde_json::from_str(&server.requests()[0].body)?;
+        assert_eq!("create", body["actions"][0]["action"]);
+        assert_eq!(Err(StorageError::Conflict), result);
+        Ok(())
+    }
+}
@@ -0,0 +1,534 @@
+mod file;
+mod git_repo;
+mod github;
+mod gitlab;
+mod templated;
+use super::decoder::DecoderError;
+pub use file::{FileConfig, FileStorage};
+pub use git_repo::{GitRepoConfig, GitRepoStorage};
+pub use github::{GithubConfig, GithubStorage};
+pub use gitlab::{GitlabConfig, GitlabStorage, Project};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::io;
+pub use templated::{PathTemplate, TemplatedStorage};
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum StorageError {
+    #[error("Storage did not accept the credentials, log in again")]
+    Unauthorized,
+
+    #[error("The inbox file was not found in storage")]
+    NotFound,
+
+    /// There's no inbox file because the repository or branch it would be
+    /// in can't be found, so it mustn't be created either.
+    #[error("The repository or branch {0} was not found")]
+    MissingRepository(String),
+
+    /// The inbox changed in storage since it was loaded, so writing it would
+    /// erase someone else's changes.
+    #[error("The inbox changed in storage since it was loaded")]
+    Conflict,
+
+    #[error("Storage is limiting requests, try again later")]
+    RateLimited,
+
+    #[error("Unable to reach storage {0}")]
+    Network(String),
+
+    #[error("Storage sent an unexpected response {0}")]
+    InvalidResponse(String),
+
+    #[error("Unable to use the local inbox {0}")]
+    Local(String),
+
+    #[error("This storage can't {0}")]
+    Unsupported(String),
+}
+
+/// A file next to the inbox, like its archive, written along with it. The
+/// path is relative to the inbox's folder and `%s` stands for the inbox's
+/// file name, the way org-archive-location names files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: String,
+    pub contents: String,
+    pub created: bool,
+}
+
+impl From<DecoderError> for StorageError {
+    fn from(err: DecoderError) -> Self {
+        StorageError::InvalidResponse(err.to_string())
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => StorageError::NotFound,
+            _ => StorageError::Local(err.to_string()),
+        }
+    }
+}
+
+pub trait Storage {
+    fn update(&self, inbox: &String) -> Result<(), StorageError>;
+    fn load(&self) -> Result<String, StorageError>;
+
+    /// Writes the inbox when `load` found no file. Backends that need a
+    /// different call to make a new file override this, and report a file
+    /// that has appeared since as a conflict.
+    fn create(&self, inbox: &String) -> Result<(), StorageError> {
+        self.update(inbox)
+    }
+
+    /// Writes an inbox that has `added` new reminders in one go. Backends
+    /// that can say so in their history override this.
+    fn update_batch(&self, inbox: &String, _added: usize) -> Result<(), StorageError> {
+        self.update(inbox)
+    }
+
+    /// Writes an inbox where existing reminders were changed rather than
+    /// added. Backends that keep history use `message` to say what changed.
+    fn update_with_message(&self, inbox: &String, _message: &str) -> Result<(), StorageError> {
+        self.update(inbox)
+    }
+
+    /// Reads a file next to the inbox, named the way `FileChange` names it.
+    fn load_file(&self, _path: &str) -> Result<String, StorageError> {
+        Err(StorageError::Unsupported("read other files".to_string()))
+    }
+
+    /// Writes the inbox along with other files next to it, in one commit
+    /// where the backend can.
+    fn update_with_files(
+        &self,
+        _inbox: &String,
+        _files: &[FileChange],
+        _message: &str,
+    ) -> Result<(), StorageError> {
+        Err(StorageError::Unsupported("write other files".to_string()))
+    }
+}
+
+impl<T: Storage + ?Sized> Storage for Box<T> {
+    fn update(&self, inbox: &String) -> Result<(), StorageError> {
+        (**self).update(inbox)
+    }
+
+    fn create(&self, inbox: &String) -> Result<(), StorageError> {
+        (**self).create(inbox)
+    }
+
+    fn update_batch(&self, inbox: &String, added: usize) -> Result<(), StorageError> {
+        (**self).update_batch(inbox, added)
+    }
+
+    fn update_with_message(&self, inbox: &String, message: &str) -> Result<(), StorageError> {
+        (**self).update_with_message(inbox, message)
+    }
+
+    fn load(&self) -> Result<String, StorageError> {
+        (**self).load()
+    }
+
+    fn load_file(&self, path: &str) -> Result<String, StorageError> {
+        (**self).load_file(path)
+    }
+
+    fn update_with_files(
+        &self,
+        inbox: &String,
+        files: &[FileChange],
+        message: &str,
+    ) -> Result<(), StorageError> {
+        (**self).update_with_files(inbox, files, message)
+    }
+}
+
+/// Sorts a response from one of the HTTP backends into the error the caller
+/// can act on, passing successful responses through.
+fn check_response(response: ureq::Response) -> Result<ureq::Response, StorageError> {
+    if let Some(error) = response.synthetic_error() {
+        return Err(StorageError::Network(error.to_string()));
+    }
+
+    match response.status() {
+        200..=299 => Ok(response),
+        401 => Err(StorageError::Unauthorized),
+        403 if response.header("X-RateLimit-Remaining") == Some("0") => {
+            Err(StorageError::RateLimited)
+        }
+        403 => Err(StorageError::Unauthorized),
+        404 => Err(StorageError::NotFound),
+        409 => Err(StorageError::Conflict),
+        429 => Err(StorageError::RateLimited),
+        // The server is down or overloaded, which passes like being offline
+        status @ 500..=599 => Err(StorageError::Network(format!(
+            "status {} {}",
+            status,
+            response.into_string().unwrap_or_default()
+        ))),
+        status => Err(StorageError::InvalidResponse(format!(
+            "status {} {}",
+            status,
+            response.into_string().unwrap_or_default()
+        ))),
+    }
+}
+
+fn read_json(response: ureq::Response) -> Result<ureq::SerdeValue, StorageError> {
+    response
+        .into_json()
+        .map_err(|err| StorageError::InvalidResponse(err.to_string()))
+}
+
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+const ADDED_COMMIT_MESSAGE: &str = "Reminder(s) added from Capture app";
+
+fn batch_commit_message(added: usize) -> String {
+    format!("{} reminders added from Capture app", added)
+}
+
+/// Where a file named like `FileChange::path` is, given the inbox's path in
+/// the repository.
+fn sibling_path(inbox_path: &str, path: &str) -> String {
+    let (folder, file_name) = match inbox_path.rfind('/') {
+        Some(slash) => inbox_path.split_at(slash + 1),
+        None => ("", inbox_path),
+    };
+    format!("{}{}", folder, path.replace("%s", file_name))
+}
+
+fn default_branch() -> String {
+    "master".to_string()
+}
+
+fn encode_path_segment(segment: &str) -> String {
+    utf8_percent_encode(segment, NON_ALPHANUMERIC).to_string()
+}
+
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    pub struct MockStorage {
+        inbox: RefCell<String>,
+        files: RefCell<HashMap<String, String>>,
+        update_error: Option<StorageError>,
+        load_error: Option<StorageError>,
+        conflicts: RefCell<Vec<String>>,
+        missing: RefCell<bool>,
+        created: RefCell<bool>,
+        writes: RefCell<Vec<usize>>,
+        messages: RefCell<Vec<String>>,
+    }
+
+    impl MockStorage {
+        pub fn new() -> Self {
+            MockStorage {
+                inbox: RefCell::new("".to_string()),
+                files: RefCell::new(HashMap::new()),
+                update_error: None,
+                load_error: None,
+                conflicts: RefCell::new(Vec::new()),
+                missing: RefCell::new(false),
+                created: RefCell::new(false),
+                writes: RefCell::new(Vec::new()),
+                messages: RefCell::new(Vec::new()),
+            }
+        }
+
+        pub fn inbox(&self) -> String {
+            self.inbox.borrow().to_string()
+        }
+
+        pub fn with_file(self, path: &str, contents: &str) -> Self {
+            self.files
+                .borrow_mut()
+                .insert(path.to_string(), contents.to_string());
+            self
+        }
+
+        pub fn file(&self, path: &str) -> Option<String> {
+            self.files.borrow().get(path).cloned()
+        }
+
+        pub fn with_update_error(mut self, error: StorageError) -> Self {
+            self.update_error = Some(error);
+            self
+        }
+
+        pub fn with_inbox(self, inbox: &str) -> Self {
+            *self.inbox.borrow_mut() = inbox.to_string();
+            self
+        }
+
+        pub fn with_load_error(mut self, error: StorageError) -> Self {
+            self.load_error = Some(error);
+            self
+        }
+
+        /// The next update is rejected as a conflict because the inbox was
+        /// changed to `changed_inbox` behind our back.
+        pub fn with_conflict(self, changed_inbox: &str) -> Self {
+            self.conflicts.borrow_mut().push(changed_inbox.to_string());
+            self
+        }
+
+        /// There is no inbox file until something creates one.
+        pub fn with_missing_inbox(self) -> Self {
+            *self.missing.borrow_mut() = true;
+            self
+        }
+
+        pub fn created(&self) -> bool {
+            *self.created.borrow()
+        }
+
+        /// How many reminders each successful write added
+        pub fn writes(&self) -> Vec<usize> {
+            self.writes.borrow().clone()
+        }
+
+        /// The messages of writes that changed reminders
+        pub fn messages(&self) -> Vec<String> {
+            self.messages.borrow().clone()
+        }
+
+        pub fn as_rc(self) -> Rc<Self> {
+            Rc::new(self)
+        }
+    }
+
+    impl Storage for Rc<MockStorage> {
+        fn update(&self, inbox: &String) -> Result<(), StorageError> {
+            if *self.missing.borrow() {
+                return Err(StorageError::NotFound);
+            }
+            if !self.conflicts.borrow().is_empty() {
+                *self.inbox.borrow_mut() = self.conflicts.borrow_mut().remove(0);
+                return Err(StorageError::Conflict);
+            }
+
+            match &self.update_error {
+                None => {
+                    *self.inbox.borrow_mut() = inbox.to_string();
+                    self.writes.borrow_mut().push(1);
+                    Ok(())
+                }
+                Some(update_error) => Err(update_error.clone()),
+            }
+        }
+
+        fn update_batch(&self, inbox: &String, added: usize) -> Result<(), StorageError> {
+            self.update(inbox)?;
+            *self.writes.borrow_mut().last_mut().unwrap() = added;
+            Ok(())
+        }
+
+        fn update_with_message(&self, inbox: &String, message: &str) -> Result<(), StorageError> {
+            self.update(inbox)?;
+            *self.writes.borrow_mut().last_mut().unwrap() = 0;
+            self.messages.borrow_mut().push(message.to_string());
+            Ok(())
+        }
+
+        fn load_file(&self, path: &str) -> Result<String, StorageError> {
+            self.file(path).ok_or(StorageError::NotFound)
+        }
+
+        fn update_with_files(
+            &self,
+            inbox: &String,
+            files: &[FileChange],
+            message: &str,
+        ) -> Result<(), StorageError> {
+            for file in files {
+                if file.created == self.files.borrow().contains_key(&file.path) {
+                    return Err(StorageError::Conflict);
+                }
+            }
+            self.update_with_message(inbox, message)?;
+            for file in files {
+                self.files
+                    .borrow_mut()
+                    .insert(file.path.clone(), file.contents.clone());
+            }
+            Ok(())
+        }
+
+        fn create(&self, inbox: &String) -> Result<(), StorageError> {
+            if !*self.missing.borrow() {
+                return Err(StorageError::Conflict);
+            }
+            *self.missing.borrow_mut() = false;
+            *self.created.borrow_mut() = true;
+            *self.inbox.borrow_mut() = inbox.to_string();
+            Ok(())
+        }
+
+        fn load(&self) -> Result<String, StorageError> {
+            if *self.missing.borrow() {
+                return Err(StorageError::NotFound);
+            }
+            match &self.load_error {
+                None => Ok(self.inbox.borrow().to_string()),
+                Some(err) => Err(err.clone()),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct RecordedRequest {
+        pub method: String,
+        pub path: String,
+        pub body: String,
+    }
+
+    pub struct StubResponse {
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: String,
+    }
+
+    impl StubResponse {
+        pub fn new(status: u16, body: &str) -> Self {
+            StubResponse {
+                status,
+                headers: Vec::new(),
+                body: body.to_string(),
+            }
+        }
+
+        pub fn with_header(mut self, name: &str, value: &str) -> Self {
+            self.headers.push((name.to_string(), value.to_string()));
+            self
+        }
+    }
+
+    /// A local HTTP stand-in that answers each connection with the next canned
+    /// response and records what it was sent.
+    pub struct StubServer {
+        port: u16,
+        requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    }
+
+    impl StubServer {
+        pub fn start(responses: Vec<(u16, String)>) -> Self {
+            StubServer::start_with(
+                responses
+                    .into_iter()
+                    .map(|(status, body)| StubResponse::new(status, &body))
+                    .collect(),
+            )
+        }
+
+        pub fn start_with(responses: Vec<StubResponse>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").expect("Could not bind stub server");
+            let port = listener.local_addr().unwrap().port();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded_requests = Arc::clone(&requests);
+
+            thread::spawn(move || {
+                for response in responses {
+                    let (stream, _) = match listener.accept() {
+                        Ok(connection) => connection,
+                        Err(_) => return,
+                    };
+                    let mut reader = BufReader::new(stream);
+                    let request = read_request(&mut reader);
+                    recorded_requests.lock().unwrap().push(request);
+
+                    let headers = response
+                        .headers
+                        .iter()
+                        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+                        .collect::<String>();
+                    let response = format!(
+                        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+                        response.status,
+                        response.body.len(),
+                        headers,
+                        response.body
+                    );
+                    let _ = reader.get_mut().write_all(response.as_bytes());
+                }
+            });
+
+            StubServer { port, requests }
+        }
+
+        pub fn url(&self) -> String {
+            format!("http://127.0.0.1:{}", self.port)
+        }
+
+        pub fn requests(&self) -> Vec<RecordedRequest> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn read_request(reader: &mut impl BufRead) -> RecordedRequest {
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        RecordedRequest {
+            method,
+            path,
+            body: String::from_utf8_lossy(&body).to_string(),
+        }
+    }
+
+    #[test]
+    fn sibling_paths_are_next_to_the_inbox() {
+        assert_eq!(
+            "gtd/inbox.org_archive",
+            sibling_path("gtd/inbox.org", "%s_archive")
+        );
+        assert_eq!("archive.org", sibling_path("inbox.org", "archive.org"));
+        assert_eq!(
+            "gtd/old/done.org",
+            sibling_path("gtd/inbox.org", "old/done.org")
+        );
+    }
+}
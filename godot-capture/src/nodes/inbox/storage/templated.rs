@@ -0,0 +1,204 @@
+use super::{FileChange, Storage, StorageError};
+use crate::nodes::inbox::metadata::Clock;
+use chrono::NaiveDate;
+use std::cell::RefCell;
+use std::fmt;
+
+const PLACEHOLDERS: [&str; 4] = ["{yyyy}", "{yy}", "{mm}", "{dd}"];
+
+/// An inbox path with the date in it, like `journal/{yyyy}/{yyyy}-{mm}-{dd}.org`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathTemplate(String);
+
+impl PathTemplate {
+    pub fn new(template: &str) -> Result<Self, String> {
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let placeholder = match rest[start..].find('}') {
+                Some(end) => &rest[start..start + end + 1],
+                None => &rest[start..],
+            };
+            if !PLACEHOLDERS.contains(&placeholder) {
+                return Err(format!(
+                    "{} in the inbox path {} isn't one of {}",
+                    placeholder,
+                    template,
+                    PLACEHOLDERS.join(", ")
+                ));
+            }
+            rest = &rest[start + placeholder.len()..];
+        }
+        Ok(PathTemplate(template.to_string()))
+    }
+
+    /// A path without placeholders is the same file every day.
+    pub fn is_fixed(&self) -> bool {
+        !self.0.contains('{')
+    }
+
+    pub fn resolve(&self, date: NaiveDate) -> String {
+        self.0
+            .replace("{yyyy}", &date.format("%Y").to_string())
+            .replace("{yy}", &date.format("%y").to_string())
+            .replace("{mm}", &date.format("%m").to_string())
+            .replace("{dd}", &date.format("%d").to_string())
+    }
+}
+
+type OpenStorage = Box<dyn Fn(&str) -> Box<dyn Storage + Send> + Send>;
+
+/// Storage for an inbox whose path is a template, opening the file for the
+/// day with `open` every time the inbox is loaded. Writing after the day has
+/// changed is a conflict, so the inbox is loaded again from the new day's
+/// file, which the write then creates.
+pub struct TemplatedStorage {
+    template: PathTemplate,
+    clock: Box<dyn Clock>,
+    open: OpenStorage,
+    current: RefCell<Option<(String, Box<dyn Storage + Send>)>>,
+}
+
+impl TemplatedStorage {
+    pub fn new(template: PathTemplate, clock: Box<dyn Clock>, open: OpenStorage) -> Self {
+        TemplatedStorage {
+            template,
+            clock,
+            open,
+            current: RefCell::new(None),
+        }
+    }
+
+    fn path(&self) -> String {
+        self.template.resolve(self.clock.now().date())
+    }
+
+    // The storage for the file that was loaded, as long as it is still the
+    // file for today.
+    fn write<F>(&self, write: F) -> Result<(), StorageError>
+    where
+        F: FnOnce(&dyn Storage) -> Result<(), StorageError>,
+    {
+        match &*self.current.borrow() {
+            Some((path, storage)) if *path == self.path() => write(&**storage),
+            _ => Err(StorageError::Conflict),
+        }
+    }
+}
+
+impl fmt::Debug for TemplatedStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TemplatedStorage")
+            .field("template", &self.template)
+            .field("clock", &self.clock)
+            .finish()
+    }
+}
+
+impl Storage for TemplatedStorage {
+    fn update(&self, inbox: &String) -> Result<(), StorageError> {
+        self.write(|storage| storage.update(inbox))
+    }
+
+    fn create(&self, inbox: &String) -> Result<(), StorageError> {
+        self.write(|storage| storage.create(inbox))
+    }
+
+    fn update_batch(&self, inbox: &String, added: usize) -> Result<(), StorageError> {
+        self.write(|storage| storage.update_batch(inbox, added))
+    }
+
+    fn update_with_message(&self, inbox: &String, message: &str) -> Result<(), StorageError> {
+        self.write(|storage| storage.update_with_message(inbox, message))
+    }
+
+    fn load(&self) -> Result<String, StorageError> {
+        let path = self.path();
+        let mut current = self.current.borrow_mut();
+        if current.as_ref().map(|(current_path, _)| current_path) != Some(&path) {
+            *current = Some((path.clone(), (self.open)(&path)));
+        }
+        match &*current {
+            Some((_, storage)) => storage.load(),
+            None => Err(StorageError::NotFound),
+        }
+    }
+
+    fn load_file(&self, path: &str) -> Result<String, StorageError> {
+        match &*self.current.borrow() {
+            Some((_, storage)) => storage.load_file(path),
+            None => Err(StorageError::NotFound),
+        }
+    }
+
+    fn update_with_files(
+        &self,
+        inbox: &String,
+        files: &[FileChange],
+        message: &str,
+    ) -> Result<(), StorageError> {
+        self.write(|storage| storage.update_with_files(inbox, files, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::metadata::tests::capture_time;
+    use crate::nodes::inbox::storage::FileStorage;
+    use crate::nodes::inbox::Inbox;
+    use chrono::{Duration, NaiveDateTime};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Clone)]
+    struct SharedClock(Arc<Mutex<NaiveDateTime>>);
+
+    impl Clock for SharedClock {
+        fn now(&self) -> NaiveDateTime {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn placeholders_are_filled_in_with_the_date() {
+        let template = PathTemplate::new("journal/{yyyy}/{yy}{mm}{dd}.org").unwrap();
+
+        assert_eq!(
+            "journal/2026/261018.org",
+            template.resolve(capture_time().date())
+        );
+        assert!(!template.is_fixed());
+        assert!(PathTemplate::new("gtd/inbox.org").unwrap().is_fixed());
+    }
+
+    #[test]
+    fn unknown_placeholders_are_rejected() {
+        assert!(PathTemplate::new("journal/{date}.org").is_err());
+        assert!(PathTemplate::new("journal/{yyyy.org").is_err());
+    }
+
+    #[test]
+    fn each_day_goes_in_its_own_file() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().to_path_buf();
+        let clock = SharedClock(Arc::new(Mutex::new(capture_time())));
+        let storage = TemplatedStorage::new(
+            PathTemplate::new("journal/{yyyy}/{yyyy}-{mm}-{dd}.org")?,
+            Box::new(clock.clone()),
+            Box::new(move |path| Box::new(FileStorage::new(root.join(path)))),
+        );
+
+        let mut inbox = Inbox::load(storage)?;
+        inbox.save("one")?;
+        *clock.0.lock().unwrap() = capture_time() + Duration::days(1);
+        inbox.save("two")?;
+
+        let journal = dir.path().join("journal").join("2026");
+        let read = |name: &str| fs::read_to_string(journal.join(PathBuf::from(name)));
+        assert_eq!("* Inbox\n** one", read("2026-10-18.org")?);
+        assert_eq!("* Inbox\n** two", read("2026-10-19.org")?);
+        assert_eq!("two", inbox.reminders());
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use serde::Deserialize;
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Position {
+    Append,
+    Prepend,
+}
+
+/// Where reminders go in the org file, like org-capture's `file+olp`. The
+/// outline is a `/` separated path of headline titles, missing headlines are
+/// created. A datetree target, like `file+olp+datetree`, files reminders under
+/// the day they are captured on below the outline.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "TargetConfig")]
+pub struct Target {
+    outline: Vec<String>,
+    level: Option<usize>,
+    position: Position,
+    datetree: bool,
+}
+
+impl Target {
+    pub fn new(outline: &str, level: Option<usize>, position: Position) -> Result<Self, String> {
+        let outline = parse_outline(outline)?;
+        if let Some(level) = level {
+            if level <= outline.len() {
+                return Err(format!(
+                    "reminders under {} need a level of at least {}",
+                    outline.join("/"),
+                    outline.len() + 1
+                ));
+            }
+        }
+
+        Ok(Target {
+            outline,
+            level,
+            position,
+            datetree: false,
+        })
+    }
+
+    /// A datetree under the outline, or at the top level of the file when the
+    /// outline is empty.
+    pub fn datetree(outline: &str, position: Position) -> Result<Self, String> {
+        let outline = if outline.trim().is_empty() {
+            Vec::new()
+        } else {
+            parse_outline(outline)?
+        };
+
+        Ok(Target {
+            outline,
+            level: None,
+            position,
+            datetree: true,
+        })
+    }
+
+    pub fn outline(&self) -> &[String] {
+        &self.outline
+    }
+
+    /// The level for a reminder under a headline at `parent_level`. The
+    /// configured level is only used when it is deeper than the parent, which
+    /// it might not be if the file nests the outline differently.
+    pub fn level_under(&self, parent_level: usize) -> usize {
+        self.level
+            .filter(|level| *level > parent_level)
+            .unwrap_or(parent_level + 1)
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn is_datetree(&self) -> bool {
+        self.datetree
+    }
+}
+
+fn parse_outline(outline: &str) -> Result<Vec<String>, String> {
+    let outline = outline
+        .split('/')
+        .map(|title| title.trim().to_string())
+        .collect::<Vec<String>>();
+    if outline.iter().any(|title| title.is_empty()) {
+        return Err("the target outline needs a headline title at every level".to_string());
+    }
+    Ok(outline)
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target {
+            outline: vec!["Inbox".to_string()],
+            level: None,
+            position: Position::Append,
+            datetree: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TargetConfig {
+    #[serde(default)]
+    outline: Option<String>,
+    #[serde(default)]
+    level: Option<usize>,
+    #[serde(default = "default_position")]
+    position: Position,
+    #[serde(default)]
+    datetree: bool,
+}
+
+fn default_position() -> Position {
+    Position::Append
+}
+
+impl TryFrom<TargetConfig> for Target {
+    type Error = String;
+
+    // A datetree goes at the top level unless there is an outline, anything
+    // else under `Inbox`.
+    fn try_from(config: TargetConfig) -> Result<Self, Self::Error> {
+        if !config.datetree {
+            let outline = config.outline.as_deref().unwrap_or("Inbox");
+            return Target::new(outline, config.level, config.position);
+        }
+        if config.level.is_some() {
+            return Err("reminders in a datetree always go right under the day".to_string());
+        }
+        Target::datetree(config.outline.as_deref().unwrap_or(""), config.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_outline_is_split_on_slashes() {
+        let target = Target::new("Work / Inbox", None, Position::Append).unwrap();
+
+        assert_eq!(vec!["Work", "Inbox"], target.outline());
+        assert_eq!(3, target.level_under(2));
+    }
+
+    #[test]
+    fn empty_headline_titles_are_rejected() {
+        assert!(Target::new("", None, Position::Append).is_err());
+        assert!(Target::new("Work//Inbox", None, Position::Append).is_err());
+    }
+
+    #[test]
+    fn the_level_has_to_be_below_the_outline() {
+        assert!(Target::new("Work/Inbox", Some(2), Position::Append).is_err());
+
+        let target = Target::new("Work/Inbox", Some(4), Position::Append).unwrap();
+        assert_eq!(4, target.level_under(2));
+        assert_eq!(6, target.level_under(5));
+    }
+
+    #[test]
+    fn deserializes_with_defaults() -> Result<(), serde_json::Error> {
+        let target: Target = serde_json::from_str(r#"{"position": "prepend"}"#)?;
+
+        assert_eq!(vec!["Inbox"], target.outline());
+        assert_eq!(Position::Prepend, target.position());
+        Ok(())
+    }
+
+    #[test]
+    fn a_datetree_goes_at_the_top_level_unless_given_an_outline() -> Result<(), serde_json::Error> {
+        let target: Target = serde_json::from_str(r#"{"datetree": true}"#)?;
+        assert!(target.is_datetree());
+        assert!(target.outline().is_empty());
+
+        let target: Target = serde_json::from_str(r#"{"datetree": true, "outline": "Journal"}"#)?;
+        assert_eq!(vec!["Journal"], target.outline());
+
+        assert!(serde_json::from_str::<Target>(r#"{"datetree": true, "level": 5}"#).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+use super::capture_text::{CaptureText, TodoKeywords};
+use super::dates::{Planning, PlanningKind};
+use super::format::{shown_keyword, InboxFormat};
+use super::org::{Headline, OrgDocument};
+use chrono::NaiveDateTime;
+
+/// TaskPaper, an `Inbox:` project with a `- reminder` task for each reminder.
+/// Projects and tasks are headlines one level deeper for every tab they are
+/// indented by, and anything else is a note under them.
+#[derive(Debug)]
+pub struct TaskPaperFormat;
+
+impl InboxFormat for TaskPaperFormat {
+    fn parse(&self, text: &str) -> OrgDocument {
+        OrgDocument::parse_with(text, |line| {
+            let indent = leading_tabs(line);
+            let rest = &line[indent..];
+            if rest == "-" || rest.starts_with("- ") {
+                return Some((indent + 1, indent + 1..line.len()));
+            }
+            project_colon(rest).map(|colon| (indent + 1, indent..indent + colon))
+        })
+    }
+
+    fn heading(&self, level: usize, title: &str) -> Headline {
+        let indent = tabs(level);
+        let line = format!("{}{}:", indent, title);
+        let title_range = indent.len()..indent.len() + title.len();
+        Headline::with_line(level, line, title_range)
+    }
+
+    // Tags, the priority and dates are all TaskPaper tags, with a scheduled
+    // date being when the task starts.
+    fn reminder(
+        &self,
+        _parent_level: usize,
+        level: usize,
+        text: &CaptureText,
+        planning: Option<&Planning>,
+        keywords: &TodoKeywords,
+    ) -> Headline {
+        let mut words = Vec::new();
+        if let Some(keyword) = shown_keyword(text, keywords) {
+            words.push(keyword.to_string());
+        }
+        if !text.title.is_empty() {
+            words.push(text.title.clone());
+        }
+        words.extend(text.tags.iter().map(|tag| format!("@{}", tag)));
+        if let Some(priority) = text.priority {
+            words.push(format!("@priority({})", priority));
+        }
+        if let Some(planning) = planning {
+            let tag = match planning.kind {
+                PlanningKind::Deadline => "due",
+                PlanningKind::Scheduled => "start",
+            };
+            let mut date = planning.date.format("%Y-%m-%d").to_string();
+            if let Some(time) = planning.time {
+                date = format!("{} {}", date, time.format("%H:%M"));
+            }
+            words.push(format!("@{}({})", tag, date));
+        }
+        let done = text
+            .keyword
+            .as_ref()
+            .map_or(false, |keyword| keywords.is_done(keyword));
+        if done {
+            words.push("@done".to_string());
+        }
+
+        Headline::with_prefix(level, &format!("{}-", tabs(level)), &words.join(" "))
+    }
+
+    // Notes are indented one tab more than what they are under. One that
+    // would read as a task or a project is escaped.
+    fn push_body_line(&self, headline: &mut Headline, text: &str) {
+        let text = self.escape_body_line(text);
+        let line = if text.is_empty() {
+            text
+        } else {
+            format!("{}{}", tabs(headline.level() + 1), text)
+        };
+        headline.body_mut().push(line);
+    }
+
+    fn escape_body_line(&self, text: &str) -> String {
+        let text = text.trim();
+        if text.starts_with('-') || project_colon(text).is_some() {
+            format!("\\{}", text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn property<'a>(&self, headline: &'a Headline, name: &str) -> Option<&'a str> {
+        tags(headline.line())
+            .into_iter()
+            .find(|tag| tag.name.eq_ignore_ascii_case(name))
+            .map(|tag| tag.value.unwrap_or(""))
+    }
+
+    fn set_property(&self, headline: &mut Headline, name: &str, value: &str) {
+        let tag = format!("@{}({})", name.to_lowercase(), value);
+        let line = headline.line();
+        let line = match tags(line)
+            .into_iter()
+            .find(|tag| tag.name.eq_ignore_ascii_case(name))
+        {
+            Some(existing) => format!(
+                "{}{}{}",
+                &line[..existing.start],
+                tag,
+                &line[existing.end..]
+            ),
+            None => format!("{} {}", line, tag),
+        };
+        set_line(headline, line);
+    }
+
+    fn is_done(&self, headline: &Headline, _keywords: &TodoKeywords) -> bool {
+        self.property(headline, "done").is_some()
+    }
+
+    fn mark_done(
+        &self,
+        headline: &mut Headline,
+        _keywords: &TodoKeywords,
+        closed_at: NaiveDateTime,
+    ) {
+        let line = format!(
+            "{} @done({})",
+            headline.line(),
+            closed_at.format("%Y-%m-%d")
+        );
+        set_line(headline, line);
+    }
+
+    // Tabs are added or taken away from everything under the headline too.
+    fn set_level(&self, headline: &mut Headline, level: usize) {
+        move_by(headline, level as isize - headline.level() as isize);
+    }
+}
+
+fn move_by(headline: &mut Headline, levels: isize) {
+    let level = (headline.level() as isize + levels).max(1) as usize;
+    let indent = leading_tabs(headline.line());
+    let new_indent = tabs(level);
+    let line = format!("{}{}", new_indent, &headline.line()[indent..]);
+    let title = headline.title_range();
+    let title_range =
+        title.start - indent + new_indent.len()..title.end - indent + new_indent.len();
+    headline.set_line(level, line, title_range);
+
+    for line in headline.body_mut().iter_mut() {
+        if line.is_empty() {
+            continue;
+        }
+        let indent = leading_tabs(line) as isize;
+        let new_indent = (indent + levels).max(0) as usize;
+        *line = format!("{}{}", "\t".repeat(new_indent), &line[indent as usize..]);
+    }
+    for child in headline.children_mut().iter_mut() {
+        move_by(child, levels);
+    }
+}
+
+// Tasks have their title up to the end of the line.
+fn set_line(headline: &mut Headline, line: String) {
+    let level = headline.level();
+    let start = headline.title_range().start;
+    let end = line.len();
+    headline.set_line(level, line, start..end);
+}
+
+// Tabs for the start of a headline at `level`.
+fn tabs(level: usize) -> String {
+    "\t".repeat(level.saturating_sub(1))
+}
+
+fn leading_tabs(line: &str) -> usize {
+    line.len() - line.trim_start_matches('\t').len()
+}
+
+// A project ends with a colon, which tags can follow.
+fn project_colon(text: &str) -> Option<usize> {
+    if text.starts_with('\\') || text.starts_with("- ") {
+        return None;
+    }
+    let end = tags(text)
+        .iter()
+        .rev()
+        .fold(text.trim_end().len(), |end, tag| {
+            if text[tag.end..end].trim().is_empty() {
+                tag.start
+            } else {
+                end
+            }
+        });
+    let before_tags = text[..end].trim_end();
+    if before_tags.len() > 1 && before_tags.ends_with(':') {
+        Some(before_tags.len() - 1)
+    } else {
+        None
+    }
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    value: Option<&'a str>,
+    start: usize,
+    end: usize,
+}
+
+// `@name` or `@name(value)` at the start of a word
+fn tags(line: &str) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    let mut from = 0;
+    while let Some(at) = line[from..].find('@').map(|at| from + at) {
+        from = at + 1;
+        if !line[..at].chars().last().map_or(true, char::is_whitespace) {
+            continue;
+        }
+        let name_end = line[from..]
+            .find(|c: char| !(c.is_alphanumeric() || "-_.".contains(c)))
+            .map_or(line.len(), |end| from + end);
+        if name_end == from {
+            continue;
+        }
+        let mut tag = Tag {
+            name: &line[from..name_end],
+            value: None,
+            start: at,
+            end: name_end,
+        };
+        if line[name_end..].starts_with('(') {
+            if let Some(close) = line[name_end..].find(')') {
+                tag.value = Some(&line[name_end + 1..name_end + close]);
+                tag.end = name_end + close + 1;
+            }
+        }
+        from = tag.end;
+        tags.push(tag);
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::metadata::tests::capture_time;
+
+    #[test]
+    fn reads_projects_tasks_and_notes_and_writes_them_back_unchanged() {
+        let text = "Inbox: @home\n\n\t- one @due(2026-10-19 09:00)\n\t\ta note\n\t\t- a step\n\tLater:\n\t\t- two\nnot a project: really\n";
+
+        let document = TaskPaperFormat.parse(text);
+
+        assert_eq!(text, document.to_string());
+        let inbox = &document.headlines()[0];
+        assert_eq!("Inbox", inbox.title());
+        assert_eq!("one @due(2026-10-19 09:00)", inbox.children()[0].title());
+        assert_eq!(vec!["\t\ta note"], inbox.children()[0].body().to_vec());
+        assert_eq!("a step", inbox.children()[0].children()[0].title());
+        assert_eq!("Later", inbox.children()[1].title());
+        assert_eq!(
+            vec!["not a project: really"],
+            inbox.children()[1].children()[0].body().to_vec()
+        );
+    }
+
+    #[test]
+    fn writes_tags_priorities_and_dates() {
+        let keywords = TodoKeywords::new(&["TODO", "WAITING", "|", "DONE"]);
+        let text = CaptureText::parse("WAITING Call mom #phone [#B]", &keywords);
+        let planning = Planning {
+            kind: PlanningKind::Scheduled,
+            date: capture_time().date(),
+            time: Some(capture_time().time()),
+        };
+
+        let reminder = TaskPaperFormat.reminder(1, 2, &text, Some(&planning), &keywords);
+
+        assert_eq!(
+            "\t- WAITING Call mom @phone @priority(B) @start(2026-10-18 09:12)",
+            reminder.line()
+        );
+        assert_eq!("Inbox:", TaskPaperFormat.heading(1, "Inbox").line());
+    }
+
+    #[test]
+    fn notes_are_indented_and_escaped() {
+        let mut reminder = TaskPaperFormat.parse("\t- one\n").headlines()[0].clone();
+
+        TaskPaperFormat.push_body_line(&mut reminder, "- bring the list");
+        TaskPaperFormat.push_body_line(&mut reminder, "Shopping:");
+        TaskPaperFormat.push_body_line(&mut reminder, "milk");
+
+        assert_eq!(
+            vec!["\t\t\\- bring the list", "\t\t\\Shopping:", "\t\tmilk"],
+            reminder.body().to_vec()
+        );
+    }
+
+    #[test]
+    fn properties_and_done_are_tags() {
+        let keywords = TodoKeywords::default();
+        let mut reminder = TaskPaperFormat.parse("- one @id(1) @home\n").headlines()[0].clone();
+
+        TaskPaperFormat.set_property(&mut reminder, "ID", "2");
+        TaskPaperFormat.set_property(&mut reminder, "CAPTURED_FROM", "Pixel 7");
+        assert!(!TaskPaperFormat.is_done(&reminder, &keywords));
+        TaskPaperFormat.mark_done(&mut reminder, &keywords, capture_time());
+
+        assert_eq!(
+            "- one @id(2) @home @captured_from(Pixel 7) @done(2026-10-18)",
+            reminder.line()
+        );
+        assert_eq!(Some("2"), TaskPaperFormat.property(&reminder, "ID"));
+        assert!(TaskPaperFormat.is_done(&reminder, &keywords));
+    }
+
+    #[test]
+    fn a_moved_task_takes_its_notes_and_subtasks_along() {
+        let document = TaskPaperFormat.parse("Inbox:\n\t- one\n\t\tnote\n\t\t- step\n");
+        let mut reminder = document.headlines()[0].children()[0].clone();
+
+        TaskPaperFormat.set_level(&mut reminder, 1);
+
+        let mut archive = TaskPaperFormat.parse("");
+        archive.push_headline(reminder);
+        assert_eq!("- one\n\tnote\n\t- step", archive.to_string());
+        assert_eq!(TaskPaperFormat.parse("- one\n\tnote\n\t- step"), archive);
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// A note to save, with the name of the capture template to save it with and
+/// the answers to that template's prompts in the order they were asked. A
+/// note with an ID is saved with it as its `:ID:`, and only once.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub answers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+impl Note {
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn with_template(mut self, template: &str, answers: Vec<String>) -> Self {
+        self.template = Some(template.to_string());
+        self.answers = answers;
+        self
+    }
+}
+
+impl From<&str> for Note {
+    fn from(text: &str) -> Self {
+        Note {
+            text: text.to_string(),
+            ..Note::default()
+        }
+    }
+}
+
+/// A named template for new reminders, written like an org-capture one.
+///
+/// - `%?` is the first line of the note and `%i` the lines after it.
+/// - `%U` and `%u` are inactive timestamps of when it was captured, with and
+///   without the time, and `%T` and `%t` active ones.
+/// - `%^{Prompt}` is the answer to a prompt, and `%^{Prompt|default}` has a
+///   default for when it isn't answered.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CaptureTemplate {
+    pub name: String,
+    pub template: String,
+}
+
+impl CaptureTemplate {
+    /// The prompts to ask before saving, each once.
+    pub fn prompts(&self) -> Vec<&str> {
+        let mut prompts = Vec::new();
+        for (prompt, _) in prompts_in(&self.template) {
+            if !prompts.contains(&prompt) {
+                prompts.push(prompt);
+            }
+        }
+        prompts
+    }
+
+    /// False when the template has no `%i` for the lines after the first,
+    /// which then go under the reminder the way they do without a template.
+    pub fn places_body(&self) -> bool {
+        self.template.contains("%i")
+    }
+
+    /// Fills in the template. The lines of `%i` after the first are indented
+    /// like the line it is on. What the note and answers put at the start of
+    /// a line goes through `escape`, so it can't be read as markup.
+    pub fn render(
+        &self,
+        title: &str,
+        body: &[&str],
+        answers: &[String],
+        captured_at: NaiveDateTime,
+        escape: &dyn Fn(&str) -> String,
+    ) -> String {
+        let prompts = self.prompts();
+        let mut rendered = String::new();
+        let mut rest = self.template.as_str();
+        while let Some(percent) = rest.find('%') {
+            rendered.push_str(&rest[..percent]);
+            rest = &rest[percent..];
+            let line_start = rendered.rfind('\n').map_or(0, |newline| newline + 1);
+            let line = &rendered[line_start..];
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let starts_line = indent.len() == line.len();
+            let typed = |text: &str| {
+                if starts_line {
+                    escape(text)
+                } else {
+                    text.to_string()
+                }
+            };
+            let placeholder = rest.chars().nth(1);
+            let filled_in = match placeholder {
+                Some('?') => typed(title),
+                Some('i') => {
+                    let mut lines = body.iter();
+                    let mut filled_in = lines.next().map_or_else(String::new, |first| typed(first));
+                    for line in lines {
+                        filled_in.push_str(&format!("\n{}{}", indent, escape(line)));
+                    }
+                    filled_in
+                }
+                Some('U') => captured_at.format("[%Y-%m-%d %a %H:%M]").to_string(),
+                Some('u') => captured_at.format("[%Y-%m-%d %a]").to_string(),
+                Some('T') => captured_at.format("<%Y-%m-%d %a %H:%M>").to_string(),
+                Some('t') => captured_at.format("<%Y-%m-%d %a>").to_string(),
+                Some('^') => match prompt_at(rest) {
+                    Some((prompt, default, length)) => {
+                        let answer = prompts
+                            .iter()
+                            .position(|asked| *asked == prompt)
+                            .and_then(|index| answers.get(index))
+                            .map(|answer| answer.trim())
+                            .filter(|answer| !answer.is_empty())
+                            .unwrap_or(default);
+                        rendered.push_str(&typed(answer));
+                        rest = &rest[length..];
+                        continue;
+                    }
+                    None => "%^".to_string(),
+                },
+                // Anything else is left as it is
+                Some(other) => format!("%{}", other),
+                None => "%".to_string(),
+            };
+            rendered.push_str(&filled_in);
+            rest = &rest[1 + placeholder.map_or(0, char::len_utf8)..];
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+fn prompts_in(template: &str) -> Vec<(&str, &str)> {
+    let mut prompts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("%^{") {
+        rest = &rest[start..];
+        match prompt_at(rest) {
+            Some((prompt, default, length)) => {
+                prompts.push((prompt, default));
+                rest = &rest[length..];
+            }
+            None => rest = &rest[3..],
+        }
+    }
+    prompts
+}
+
+// `%^{Prompt|default}` at the start of `text`, with how long it is
+fn prompt_at(text: &str) -> Option<(&str, &str, usize)> {
+    let inside = text.strip_prefix("%^{")?;
+    let end = inside.find('}')?;
+    let mut options = inside[..end].split('|');
+    let prompt = options.next().unwrap_or("");
+    let default = options.next().unwrap_or("");
+    Some((prompt, default, "%^{".len() + end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::metadata::tests::capture_time;
+
+    fn unescaped(text: &str) -> String {
+        text.to_string()
+    }
+
+    fn template(template: &str) -> CaptureTemplate {
+        CaptureTemplate {
+            name: "Todo".to_string(),
+            template: template.to_string(),
+        }
+    }
+
+    #[test]
+    fn fills_in_the_note_and_timestamps() {
+        let todo = template(
+            "** TODO %?\n   :PROPERTIES:\n   :CREATED: %U\n   :END:\n   %i\n   on %t %u %T",
+        );
+
+        let rendered = todo.render(
+            "Call mom",
+            &["about", "the trip"],
+            &[],
+            capture_time(),
+            &unescaped,
+        );
+
+        assert_eq!(
+            "** TODO Call mom\n   :PROPERTIES:\n   :CREATED: [2026-10-18 Sun 09:12]\n   :END:\n   about\n   the trip\n   on <2026-10-18 Sun> [2026-10-18 Sun] <2026-10-18 Sun 09:12>",
+            rendered
+        );
+        assert!(todo.places_body());
+    }
+
+    #[test]
+    fn prompts_are_asked_once_and_answered_in_order() {
+        let meeting = template("** Meeting with %^{Who} about %^{Topic|everything}\n%^{Who} %100");
+
+        let rendered = meeting.render("", &[], &["Ana".to_string()], capture_time(), &unescaped);
+
+        assert_eq!(vec!["Who", "Topic"], meeting.prompts());
+        assert_eq!("** Meeting with Ana about everything\nAna %100", rendered);
+        assert!(!meeting.places_body());
+    }
+
+    #[test]
+    fn unfinished_placeholders_are_left_alone() {
+        let rendered = template("%^{Who %").render("", &[], &[], capture_time(), &unescaped);
+
+        assert_eq!("%^{Who %", rendered);
+    }
+}
@@ -0,0 +1,245 @@
+use super::capture_text::{CaptureText, TodoKeywords};
+use super::dates::{Planning, PlanningKind};
+use super::format::{shown_keyword, InboxFormat};
+use super::org::{Headline, OrgDocument};
+use chrono::NaiveDateTime;
+
+/// todo.txt, one task a line like `(A) 2026-10-18 Call mom +family @phone
+/// due:2026-10-23`. There are no headings, so the whole file is the inbox and
+/// every line is a reminder.
+#[derive(Debug)]
+pub struct TodoTxtFormat;
+
+impl InboxFormat for TodoTxtFormat {
+    fn parse(&self, text: &str) -> OrgDocument {
+        OrgDocument::parse_with(text, |line| {
+            Some((1, 0..line.len())).filter(|_| !line.trim().is_empty())
+        })
+    }
+
+    fn has_headings(&self) -> bool {
+        false
+    }
+
+    fn heading(&self, level: usize, title: &str) -> Headline {
+        Headline::with_prefix(level, "", title)
+    }
+
+    // Tags become contexts, and `+projects` are left in the text as they
+    // are. A deadline is `due:` and a scheduled date the `t:` threshold date.
+    fn reminder(
+        &self,
+        parent_level: usize,
+        _level: usize,
+        text: &CaptureText,
+        planning: Option<&Planning>,
+        keywords: &TodoKeywords,
+    ) -> Headline {
+        let done = text
+            .keyword
+            .as_ref()
+            .map_or(false, |keyword| keywords.is_done(keyword));
+        let mut words = Vec::new();
+        if done {
+            words.push("x".to_string());
+        }
+        match text.priority {
+            Some(priority) if !done => words.push(format!("({})", priority)),
+            _ => {}
+        }
+        if let Some(keyword) = shown_keyword(text, keywords) {
+            words.push(keyword.to_string());
+        }
+        if !text.title.is_empty() {
+            words.push(text.title.clone());
+        }
+        words.extend(text.tags.iter().map(|tag| format!("@{}", tag)));
+        if let Some(priority) = text.priority.filter(|_| done) {
+            words.push(format!("pri:{}", priority));
+        }
+        if let Some(planning) = planning {
+            let key = match planning.kind {
+                PlanningKind::Deadline => "due",
+                PlanningKind::Scheduled => "t",
+            };
+            words.push(format!("{}:{}", key, planning.date.format("%Y-%m-%d")));
+        }
+
+        Headline::with_prefix(parent_level + 1, "", &words.join(" "))
+    }
+
+    // A task can't have lines under it, so text goes on the end of the line.
+    fn push_body_line(&self, headline: &mut Headline, text: &str) {
+        let text = text.trim();
+        if !text.is_empty() {
+            let line = format!("{} {}", headline.line(), text);
+            set_line(headline, line);
+        }
+    }
+
+    // Body text goes on the task's own line, where nothing needs escaping
+    fn escape_body_line(&self, text: &str) -> String {
+        text.trim().to_string()
+    }
+
+    fn property<'a>(&self, headline: &'a Headline, name: &str) -> Option<&'a str> {
+        headline
+            .line()
+            .split_whitespace()
+            .find_map(|word| key_value(word, name))
+    }
+
+    // Properties are `key:value` words, except CREATED which is the task's
+    // creation date.
+    fn set_property(&self, headline: &mut Headline, name: &str, value: &str) {
+        if name.eq_ignore_ascii_case("CREATED") {
+            let line = with_creation_date(headline.line(), value);
+            set_line(headline, line);
+            return;
+        }
+
+        let pair = format!(
+            "{}:{}",
+            name.to_lowercase(),
+            value.split_whitespace().collect::<Vec<&str>>().join("_")
+        );
+        let mut words = headline
+            .line()
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        match words
+            .iter()
+            .position(|word| key_value(word, name).is_some())
+        {
+            Some(existing) => words[existing] = pair,
+            None => words.push(pair),
+        }
+        set_line(headline, words.join(" "));
+    }
+
+    fn is_done(&self, headline: &Headline, _keywords: &TodoKeywords) -> bool {
+        headline.line().starts_with("x ")
+    }
+
+    // The priority is kept as `pri:` the way todo.sh does it.
+    fn mark_done(
+        &self,
+        headline: &mut Headline,
+        _keywords: &TodoKeywords,
+        closed_at: NaiveDateTime,
+    ) {
+        let (priority, rest) = split_priority(headline.line());
+        let mut line = format!("x {} {}", closed_at.format("%Y-%m-%d"), rest);
+        if let Some(priority) = priority {
+            line = format!("{} pri:{}", line, priority);
+        }
+        set_line(headline, line);
+    }
+
+    fn set_level(&self, headline: &mut Headline, level: usize) {
+        let line = headline.line().to_string();
+        let end = line.len();
+        headline.set_line(level, line, 0..end);
+    }
+}
+
+fn set_line(headline: &mut Headline, line: String) {
+    let level = headline.level();
+    let end = line.len();
+    headline.set_line(level, line, 0..end);
+}
+
+fn key_value<'a>(word: &'a str, name: &str) -> Option<&'a str> {
+    let (key, value) = word.split_once(':')?;
+    Some(value).filter(|_| key.eq_ignore_ascii_case(name))
+}
+
+// `(A) ` at the start of the line
+fn split_priority(line: &str) -> (Option<char>, &str) {
+    let mut chars = line.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some('('), Some(priority), Some(')'), Some(' ')) if priority.is_ascii_uppercase() => {
+            (Some(priority), &line[4..])
+        }
+        _ => (None, line),
+    }
+}
+
+// The creation date goes after the priority, the date being the start of an
+// org timestamp like `[2026-10-18 Sun 09:12]`.
+fn with_creation_date(line: &str, timestamp: &str) -> String {
+    let date = timestamp
+        .trim_start_matches('[')
+        .split_whitespace()
+        .next()
+        .unwrap_or("");
+    match split_priority(line) {
+        (Some(priority), rest) => format!("({}) {} {}", priority, date, rest),
+        (None, rest) => format!("{} {}", date, rest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::metadata::tests::capture_time;
+
+    #[test]
+    fn every_line_is_a_reminder() {
+        let text = "(A) Call mom +family\n\nx 2026-10-17 Pay rent\n";
+
+        let document = TodoTxtFormat.parse(text);
+
+        assert_eq!(text, document.to_string());
+        assert_eq!(2, document.headlines().len());
+        assert_eq!("x 2026-10-17 Pay rent", document.headlines()[1].title());
+    }
+
+    #[test]
+    fn writes_priorities_contexts_and_dates() {
+        let keywords = TodoKeywords::default();
+        let text = CaptureText::parse("TODO Call mom +family #phone [#A]", &keywords);
+        let planning = Planning {
+            kind: PlanningKind::Deadline,
+            date: capture_time().date(),
+            time: None,
+        };
+
+        let reminder = TodoTxtFormat.reminder(0, 2, &text, Some(&planning), &keywords);
+
+        assert_eq!(
+            "(A) Call mom +family @phone due:2026-10-18",
+            reminder.line()
+        );
+        assert_eq!(1, reminder.level());
+    }
+
+    #[test]
+    fn properties_are_key_values_and_created_is_the_creation_date() {
+        let mut reminder = TodoTxtFormat.parse("(B) one\n").headlines()[0].clone();
+
+        TodoTxtFormat.set_property(&mut reminder, "CREATED", "[2026-10-18 Sun 09:12]");
+        TodoTxtFormat.set_property(&mut reminder, "ID", "1");
+        TodoTxtFormat.set_property(&mut reminder, "CAPTURED_FROM", "Pixel 7");
+        TodoTxtFormat.set_property(&mut reminder, "ID", "2");
+
+        assert_eq!(
+            "(B) 2026-10-18 one id:2 captured_from:Pixel_7",
+            reminder.line()
+        );
+        assert_eq!(Some("2"), TodoTxtFormat.property(&reminder, "ID"));
+    }
+
+    #[test]
+    fn done_tasks_start_with_an_x_and_the_date() {
+        let keywords = TodoKeywords::default();
+        let mut reminder = TodoTxtFormat.parse("(A) 2026-10-17 one\n").headlines()[0].clone();
+
+        assert!(!TodoTxtFormat.is_done(&reminder, &keywords));
+        TodoTxtFormat.mark_done(&mut reminder, &keywords, capture_time());
+
+        assert_eq!("x 2026-10-18 2026-10-17 one pri:A", reminder.line());
+        assert!(TodoTxtFormat.is_done(&reminder, &keywords));
+    }
+}
@@ -0,0 +1,82 @@
+use super::template::Note;
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum NoteError {
+    #[error("Type something to remember first")]
+    Empty,
+
+    #[error("A reminder's title has to fit on one line")]
+    MultipleLines,
+}
+
+/// Tidies a note up before it is saved, or says why it can't be. Line endings
+/// become `\n`, text is NFC normalized so the same words are the same bytes
+/// whichever keyboard typed them, and control characters other than tabs are
+/// dropped. Only a note with a template can be empty.
+pub fn validate(note: &Note) -> Result<Note, NoteError> {
+    let text = normalize(&note.text);
+    let lines = text.lines().map(str::trim_end).collect::<Vec<&str>>();
+    let text = lines.join("\n").trim().to_string();
+    if text.is_empty() && note.template.is_none() {
+        return Err(NoteError::Empty);
+    }
+
+    // Answers go in the middle of a line
+    let answers = note
+        .answers
+        .iter()
+        .map(|answer| {
+            normalize(answer)
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+        })
+        .collect();
+    Ok(Note {
+        text,
+        template: note.template.clone(),
+        answers,
+        id: note.id.clone(),
+    })
+}
+
+fn normalize(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .nfc()
+        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_are_trimmed_and_normalized() {
+        let note = Note::from("  \r\n** Cafe\u{301} \u{7}list \r\n\tmilk\t\r\n\r\n");
+
+        assert_eq!(Ok(Note::from("** Caf\u{e9} list\n\tmilk")), validate(&note));
+    }
+
+    // Escaping is up to the inbox format
+    #[test]
+    fn markup_is_kept() {
+        let note = Note::from("** * three\n#+TITLE: four");
+
+        assert_eq!(Ok(note.clone()), validate(&note));
+    }
+
+    #[test]
+    fn empty_notes_are_rejected_unless_they_have_a_template() {
+        assert_eq!(Err(NoteError::Empty), validate(&Note::from(" \r\n\t")));
+
+        let templated = Note::from("").with_template("Review", vec![" week\n 42 ".to_string()]);
+        assert_eq!(
+            Ok(Note::from("").with_template("Review", vec!["week 42".to_string()])),
+            validate(&templated)
+        );
+    }
+}
@@ -0,0 +1,647 @@
+use super::inbox::{Inbox, InboxError, ReminderChange, ReminderRef};
+use super::outbox::{Outbox, OutboxError};
+use super::storage::Storage;
+use super::template::Note;
+use super::validation::validate;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryIter};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+enum Command {
+    Load,
+    Save(Note),
+    Change(ReminderRef, ReminderChange),
+    Retry,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WorkerEvent<E> {
+    InboxLoaded {
+        reminders: String,
+        refs: Vec<ReminderRef>,
+    },
+    LoadFailed {
+        error: E,
+    },
+    ReminderQueued {
+        pending: usize,
+    },
+    ReminderSaved {
+        reminders: String,
+        refs: Vec<ReminderRef>,
+        pending: usize,
+    },
+    /// `queued` is true when the reminder is waiting in the outbox and will
+    /// be tried again, false when it was not kept anywhere.
+    SaveFailed {
+        error: E,
+        pending: usize,
+        queued: bool,
+    },
+    ReminderChanged {
+        reminders: String,
+        refs: Vec<ReminderRef>,
+    },
+    ChangeFailed {
+        error: E,
+    },
+}
+
+/// Runs inbox loads and saves on a background thread so storage round trips
+/// never block the caller. Results come back as events to poll.
+pub struct InboxWorker<E> {
+    commands: Sender<Command>,
+    events: Receiver<WorkerEvent<E>>,
+}
+
+impl<E> InboxWorker<E>
+where
+    E: From<InboxError> + From<OutboxError> + Send + 'static,
+{
+    /// `open` is called on the worker thread whenever an inbox is needed and
+    /// there isn't one yet, so it may block.
+    ///
+    /// With a `batch_window`, queued reminders are held until no new one has
+    /// been saved for that long and then written together. Batching needs
+    /// the outbox to hold them, without one every reminder is saved directly.
+    pub fn start<T, F>(open: F, outbox: Option<Outbox>, batch_window: Option<Duration>) -> Self
+    where
+        T: Storage + Send + 'static,
+        F: FnMut() -> Result<Inbox<T>, E> + Send + 'static,
+    {
+        let (commands, command_receiver) = channel();
+        let (event_sender, events) = channel();
+        let mut state = WorkerState {
+            open,
+            inbox: None,
+            outbox,
+            batch_window,
+            collecting: false,
+            events: event_sender,
+        };
+
+        thread::spawn(move || loop {
+            let command = match state.collecting_for() {
+                Some(window) => match command_receiver.recv_timeout(window) {
+                    Ok(command) => command,
+                    Err(RecvTimeoutError::Timeout) => Command::Retry,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                },
+                None => match command_receiver.recv() {
+                    Ok(command) => command,
+                    Err(_) => break,
+                },
+            };
+            let delivered = match command {
+                Command::Load => state.load(),
+                Command::Save(note) => state.save(note),
+                Command::Change(reminder, change) => state.change(reminder, change),
+                Command::Retry => state.retry(),
+            };
+            if !delivered {
+                break;
+            }
+        });
+
+        InboxWorker { commands, events }
+    }
+
+    pub fn load(&self) {
+        let _ = self.commands.send(Command::Load);
+    }
+
+    pub fn save(&self, note: &str) {
+        self.save_note(Note::from(note));
+    }
+
+    pub fn save_note(&self, note: Note) {
+        let _ = self.commands.send(Command::Save(note));
+    }
+
+    /// Changes to reminders already in the inbox are not queued, they need
+    /// storage to be reachable. The reminder is one of the `refs` of the last
+    /// event, and the change fails if it isn't in the inbox any more.
+    pub fn change(&self, reminder: ReminderRef, change: ReminderChange) {
+        let _ = self.commands.send(Command::Change(reminder, change));
+    }
+
+    pub fn retry(&self) {
+        let _ = self.commands.send(Command::Retry);
+    }
+
+    /// Events that have arrived since the last call, without waiting.
+    pub fn events(&self) -> TryIter<'_, WorkerEvent<E>> {
+        self.events.try_iter()
+    }
+
+    #[cfg(test)]
+    fn next_event(&self) -> Option<WorkerEvent<E>> {
+        self.events
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .ok()
+    }
+}
+
+struct WorkerState<T: Storage, F, E> {
+    open: F,
+    inbox: Option<Inbox<T>>,
+    outbox: Option<Outbox>,
+    batch_window: Option<Duration>,
+    collecting: bool,
+    events: Sender<WorkerEvent<E>>,
+}
+
+// Each step returns false once nobody is listening for events any more, which
+// is the signal for the thread to stop.
+impl<T, F, E> WorkerState<T, F, E>
+where
+    T: Storage,
+    F: FnMut() -> Result<Inbox<T>, E>,
+    E: From<InboxError> + From<OutboxError>,
+{
+    // While a batch is collecting the next command is only waited for until
+    // the window closes.
+    fn collecting_for(&self) -> Option<Duration> {
+        match self.batch_window {
+            Some(window) if self.collecting => Some(window),
+            _ => None,
+        }
+    }
+
+    fn load(&mut self) -> bool {
+        let event = match (self.open)() {
+            Ok(inbox) => {
+                let reminders = inbox.reminders();
+                let refs = inbox.reminder_refs();
+                self.inbox = Some(inbox);
+                WorkerEvent::InboxLoaded { reminders, refs }
+            }
+            Err(error) => WorkerEvent::LoadFailed { error },
+        };
+        self.send(event) && self.retry()
+    }
+
+    // A note that isn't valid is turned away before it is queued, since it
+    // would never leave the outbox.
+    fn save(&mut self, note: Note) -> bool {
+        let note = match validate(&note) {
+            Ok(note) => note,
+            Err(err) => {
+                let pending = self.outbox.as_ref().map_or(0, Outbox::pending);
+                return self.send(WorkerEvent::SaveFailed {
+                    error: InboxError::from(err).into(),
+                    pending,
+                    queued: false,
+                });
+            }
+        };
+        let outbox = match &mut self.outbox {
+            Some(outbox) => outbox,
+            None => return self.save_directly(&note),
+        };
+
+        match outbox.push(note, now_in_seconds()) {
+            Ok(_) => {
+                let pending = outbox.pending();
+                if !self.send(WorkerEvent::ReminderQueued { pending }) {
+                    return false;
+                }
+                if self.batch_window.is_some() {
+                    self.collecting = true;
+                    true
+                } else {
+                    self.deliver()
+                }
+            }
+            Err(err) => {
+                let pending = outbox.pending();
+                self.send(WorkerEvent::SaveFailed {
+                    error: err.into(),
+                    pending,
+                    queued: false,
+                })
+            }
+        }
+    }
+
+    fn retry(&mut self) -> bool {
+        match &self.outbox {
+            Some(outbox) if outbox.pending() > 0 => self.deliver(),
+            _ => true,
+        }
+    }
+
+    // Storage may have been unreachable when the inbox was first wanted, so
+    // try to open it again before giving up on the queue for now.
+    fn deliver(&mut self) -> bool {
+        self.collecting = false;
+        let outbox = match &mut self.outbox {
+            Some(outbox) => outbox,
+            None => return true,
+        };
+
+        let event = match open_inbox(&mut self.open, &mut self.inbox) {
+            Ok(inbox) => match replay(outbox, inbox, self.batch_window.is_some()) {
+                Ok(_) => WorkerEvent::ReminderSaved {
+                    reminders: inbox.reminders(),
+                    refs: inbox.reminder_refs(),
+                    pending: outbox.pending(),
+                },
+                Err(err) => WorkerEvent::SaveFailed {
+                    error: err.into(),
+                    pending: outbox.pending(),
+                    queued: true,
+                },
+            },
+            Err(error) => WorkerEvent::SaveFailed {
+                error,
+                pending: outbox.pending(),
+                queued: true,
+            },
+        };
+        self.send(event)
+    }
+
+    fn save_directly(&mut self, note: &Note) -> bool {
+        let event = match open_inbox(&mut self.open, &mut self.inbox) {
+            Ok(inbox) => match inbox.save_note(note) {
+                Ok(_) => WorkerEvent::ReminderSaved {
+                    reminders: inbox.reminders(),
+                    refs: inbox.reminder_refs(),
+                    pending: 0,
+                },
+                Err(err) => WorkerEvent::SaveFailed {
+                    error: err.into(),
+                    pending: 0,
+                    queued: false,
+                },
+            },
+            Err(error) => WorkerEvent::SaveFailed {
+                error,
+                pending: 0,
+                queued: false,
+            },
+        };
+        self.send(event)
+    }
+
+    fn change(&mut self, reminder: ReminderRef, change: ReminderChange) -> bool {
+        let event = match open_inbox(&mut self.open, &mut self.inbox) {
+            Ok(inbox) => match inbox
+                .find(&reminder)
+                .and_then(|index| inbox.change(index, change))
+            {
+                Ok(_) => WorkerEvent::ReminderChanged {
+                    reminders: inbox.reminders(),
+                    refs: inbox.reminder_refs(),
+                },
+                Err(err) => WorkerEvent::ChangeFailed { error: err.into() },
+            },
+            Err(error) => WorkerEvent::ChangeFailed { error },
+        };
+        self.send(event)
+    }
+
+    fn send(&self, event: WorkerEvent<E>) -> bool {
+        self.events.send(event).is_ok()
+    }
+}
+
+fn open_inbox<'a, T, F, E>(
+    open: &mut F,
+    inbox: &'a mut Option<Inbox<T>>,
+) -> Result<&'a mut Inbox<T>, E>
+where
+    T: Storage,
+    F: FnMut() -> Result<Inbox<T>, E>,
+{
+    if inbox.is_none() {
+        *inbox = Some(open()?);
+    }
+    Ok(inbox.as_mut().expect("the inbox was just opened"))
+}
+
+fn replay<T: Storage>(
+    outbox: &mut Outbox,
+    inbox: &mut Inbox<T>,
+    batched: bool,
+) -> Result<(), OutboxError> {
+    if batched {
+        outbox.replay_batched(inbox)
+    } else {
+        outbox.replay(inbox)
+    }
+}
+
+fn now_in_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::storage::{FileStorage, StorageError};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[derive(Debug, PartialEq)]
+    enum TestError {
+        Storage(StorageError),
+        Inbox(String),
+        Outbox(String),
+    }
+
+    impl From<InboxError> for TestError {
+        fn from(err: InboxError) -> Self {
+            match err.storage_error() {
+                Some(err) => TestError::Storage(err.clone()),
+                None => TestError::Inbox(err.to_string()),
+            }
+        }
+    }
+
+    impl From<OutboxError> for TestError {
+        fn from(err: OutboxError) -> Self {
+            match err {
+                OutboxError::CouldNotDeliver(err) => err.into(),
+                err => TestError::Outbox(err.to_string()),
+            }
+        }
+    }
+
+    fn inbox_file(contents: &str) -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().expect("Could not create temp dir");
+        let path = dir.path().join("inbox.org");
+        fs::write(&path, contents).expect("Could not write inbox");
+        (dir, path)
+    }
+
+    fn opener(path: &Path) -> impl FnMut() -> Result<Inbox<FileStorage>, TestError> {
+        let path = path.to_path_buf();
+        move || Ok(Inbox::load(FileStorage::new(path.clone()))?)
+    }
+
+    // Starts out unable to reach storage until the returned switch is set
+    fn offline_opener(
+        path: &Path,
+    ) -> (
+        Arc<AtomicBool>,
+        impl FnMut() -> Result<Inbox<FileStorage>, TestError>,
+    ) {
+        let online = Arc::new(AtomicBool::new(false));
+        let switch = Arc::clone(&online);
+        let mut open = opener(path);
+        let offline_open = move || {
+            if online.load(Ordering::SeqCst) {
+                open()
+            } else {
+                Err(TestError::Storage(StorageError::Network(
+                    "offline".to_string(),
+                )))
+            }
+        };
+        (switch, offline_open)
+    }
+
+    fn outbox(dir: &TempDir) -> Option<Outbox> {
+        Outbox::open(&dir.path().join("outbox.json")).ok()
+    }
+
+    fn listed(titles: &[&str]) -> Vec<ReminderRef> {
+        titles
+            .iter()
+            .enumerate()
+            .map(|(index, title)| ReminderRef {
+                index,
+                title: title.to_string(),
+                id: None,
+            })
+            .collect()
+    }
+
+    // Queued reminders are saved with a random `:ID:`
+    fn without_ids(event: Option<WorkerEvent<TestError>>) -> Option<WorkerEvent<TestError>> {
+        event.map(|event| match event {
+            WorkerEvent::ReminderSaved {
+                reminders,
+                refs,
+                pending,
+            } => WorkerEvent::ReminderSaved {
+                reminders,
+                refs: refs
+                    .into_iter()
+                    .map(|reminder| ReminderRef {
+                        id: None,
+                        ..reminder
+                    })
+                    .collect(),
+                pending,
+            },
+            other => other,
+        })
+    }
+
+    fn headlines(path: &Path) -> Result<String, std::io::Error> {
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .filter(|line| line.starts_with('*'))
+            .collect::<Vec<&str>>()
+            .join("\n"))
+    }
+
+    #[test]
+    fn load_reports_the_reminders() {
+        let (dir, path) = inbox_file("* Inbox\n** one");
+        let worker = InboxWorker::start(opener(&path), outbox(&dir), None);
+
+        worker.load();
+
+        assert_eq!(
+            Some(WorkerEvent::InboxLoaded {
+                reminders: "one".to_string(),
+                refs: listed(&["one"])
+            }),
+            worker.next_event()
+        );
+    }
+
+    #[test]
+    fn changes_go_straight_to_storage() {
+        let (dir, path) = inbox_file("* Inbox\n** one\n** two");
+        let worker = InboxWorker::start(opener(&path), outbox(&dir), None);
+
+        let one = listed(&["one", "two"]).remove(0);
+        worker.change(one.clone(), ReminderChange::Delete);
+        worker.change(one, ReminderChange::Delete);
+
+        assert_eq!(
+            Some(WorkerEvent::ReminderChanged {
+                reminders: "two".to_string(),
+                refs: listed(&["two"])
+            }),
+            worker.next_event()
+        );
+        assert_eq!(
+            Some(WorkerEvent::ChangeFailed {
+                error: TestError::Inbox(
+                    "The reminder \"one\" is no longer in the inbox".to_string()
+                )
+            }),
+            worker.next_event()
+        );
+        assert_eq!("* Inbox\n** two", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn load_failures_are_reported() {
+        let (dir, path) = inbox_file("* Inbox");
+        let (_online, open) = offline_opener(&path);
+        let worker = InboxWorker::start(open, outbox(&dir), None);
+
+        worker.load();
+
+        assert_eq!(
+            Some(WorkerEvent::LoadFailed {
+                error: TestError::Storage(StorageError::Network("offline".to_string()))
+            }),
+            worker.next_event()
+        );
+    }
+
+    #[test]
+    fn empty_notes_are_not_queued() {
+        let (dir, path) = inbox_file("* Inbox\n** one");
+        let worker = InboxWorker::start(opener(&path), outbox(&dir), None);
+
+        worker.save(" \n ");
+
+        assert_eq!(
+            Some(WorkerEvent::SaveFailed {
+                error: TestError::Inbox("Type something to remember first".to_string()),
+                pending: 0,
+                queued: false,
+            }),
+            worker.next_event()
+        );
+        assert_eq!("* Inbox\n** one", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn save_queues_the_reminder_then_saves_it() -> Result<(), std::io::Error> {
+        let (dir, path) = inbox_file("* Inbox\n** one");
+        let worker = InboxWorker::start(opener(&path), outbox(&dir), None);
+
+        worker.load();
+        worker.save("two");
+
+        worker.next_event();
+        assert_eq!(
+            Some(WorkerEvent::ReminderQueued { pending: 1 }),
+            worker.next_event()
+        );
+        assert_eq!(
+            Some(WorkerEvent::ReminderSaved {
+                reminders: "one\ntwo".to_string(),
+                refs: listed(&["one", "two"]),
+                pending: 0
+            }),
+            without_ids(worker.next_event())
+        );
+        assert_eq!("* Inbox\n** one\n** two", headlines(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn a_reminder_that_cannot_be_saved_stays_queued() {
+        let (dir, path) = inbox_file("* Inbox");
+        let (_online, open) = offline_opener(&path);
+        let worker = InboxWorker::start(open, outbox(&dir), None);
+
+        worker.save("two");
+
+        worker.next_event();
+        assert_eq!(
+            Some(WorkerEvent::SaveFailed {
+                error: TestError::Storage(StorageError::Network("offline".to_string())),
+                pending: 1,
+                queued: true
+            }),
+            worker.next_event()
+        );
+        assert_eq!(1, outbox(&dir).map(|outbox| outbox.pending()).unwrap_or(0));
+    }
+
+    #[test]
+    fn retry_delivers_reminders_once_storage_is_back() -> Result<(), std::io::Error> {
+        let (dir, path) = inbox_file("* Inbox");
+        let (online, open) = offline_opener(&path);
+        let worker = InboxWorker::start(open, outbox(&dir), None);
+
+        worker.save("one");
+        worker.next_event();
+        worker.next_event();
+        online.store(true, Ordering::SeqCst);
+        worker.retry();
+
+        assert_eq!(
+            Some(WorkerEvent::ReminderSaved {
+                reminders: "one".to_string(),
+                refs: listed(&["one"]),
+                pending: 0
+            }),
+            without_ids(worker.next_event())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn without_an_outbox_reminders_are_saved_directly() -> Result<(), std::io::Error> {
+        let (_dir, path) = inbox_file("* Inbox\n** one");
+        let worker = InboxWorker::start(opener(&path), None, None);
+
+        worker.save("two");
+
+        assert_eq!(
+            Some(WorkerEvent::ReminderSaved {
+                reminders: "one\ntwo".to_string(),
+                refs: listed(&["one", "two"]),
+                pending: 0
+            }),
+            worker.next_event()
+        );
+        assert_eq!("* Inbox\n** one\n** two", fs::read_to_string(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn batching_saves_reminders_from_the_same_window_together() -> Result<(), std::io::Error> {
+        let (dir, path) = inbox_file("* Inbox");
+        let worker = InboxWorker::start(
+            opener(&path),
+            outbox(&dir),
+            Some(Duration::from_millis(200)),
+        );
+
+        worker.save("one");
+        worker.save("two");
+
+        worker.next_event();
+        worker.next_event();
+        assert_eq!(
+            Some(WorkerEvent::ReminderSaved {
+                reminders: "one\ntwo".to_string(),
+                refs: listed(&["one", "two"]),
+                pending: 0
+            }),
+            without_ids(worker.next_event())
+        );
+        assert_eq!("* Inbox\n** one\n** two", headlines(&path)?);
+        Ok(())
+    }
+}
@@ -2,3 +2,4 @@ pub mod capture_note;
 mod inbox;
 pub mod login;
 pub mod oauth;
+mod settings;
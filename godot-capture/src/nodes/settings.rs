@@ -0,0 +1,413 @@
+use crate::nodes::inbox::{
+    ArchiveLocation, CaptureTemplate, FileConfig, GitRepoConfig, GithubConfig, GitlabConfig,
+    Metadata, PathTemplate, Target, TodoKeywords,
+};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("No settings found at {}, add your storage backend, file path and branch there", .0.display())]
+    Missing(PathBuf),
+
+    #[error("Unable to read settings {0}")]
+    Unreadable(io::Error),
+
+    #[error("Settings are invalid {0}")]
+    Invalid(#[from] serde_json::Error),
+
+    #[error("Settings are invalid {0}")]
+    InvalidPath(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageConfig {
+    Gitlab(GitlabConfig),
+    Github(GithubConfig),
+    File(FileConfig),
+    Git(GitRepoConfig),
+}
+
+impl StorageConfig {
+    /// The inbox file, which says what format it is in.
+    pub fn file_path(&self) -> String {
+        match self {
+            StorageConfig::Gitlab(config) => config.file_path.clone(),
+            StorageConfig::Github(config) => config.file_path.clone(),
+            StorageConfig::File(config) => config.path.to_string_lossy().into_owned(),
+            StorageConfig::Git(config) => config.file_path.clone(),
+        }
+    }
+
+    pub fn path_template(&self) -> Result<PathTemplate, SettingsError> {
+        PathTemplate::new(&self.file_path()).map_err(SettingsError::InvalidPath)
+    }
+
+    /// The same storage with the inbox at `path`, for a templated path
+    /// resolved to a day's file.
+    pub fn with_file_path(&self, path: &str) -> StorageConfig {
+        let mut config = self.clone();
+        match &mut config {
+            StorageConfig::Gitlab(config) => config.file_path = path.to_string(),
+            StorageConfig::Github(config) => config.file_path = path.to_string(),
+            StorageConfig::File(config) => config.path = PathBuf::from(path),
+            StorageConfig::Git(config) => config.file_path = path.to_string(),
+        }
+        config
+    }
+}
+
+/// Reminders saved within `window_seconds` of each other, and everything
+/// waiting in the offline queue, are written to storage in one commit.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BatchConfig {
+    pub window_seconds: u64,
+}
+
+impl BatchConfig {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_seconds)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Settings {
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub batching: Option<BatchConfig>,
+    #[serde(default)]
+    pub target: Target,
+    #[serde(default)]
+    pub metadata: Metadata,
+    #[serde(default)]
+    pub todo_keywords: TodoKeywords,
+    #[serde(default)]
+    pub archive: ArchiveLocation,
+    #[serde(default)]
+    pub templates: Vec<CaptureTemplate>,
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> Result<Self, SettingsError> {
+        let raw_settings = fs::read_to_string(path).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => SettingsError::Missing(path.to_path_buf()),
+            _ => SettingsError::Unreadable(err),
+        })?;
+
+        Settings::parse(&raw_settings)
+    }
+
+    pub fn parse(raw_settings: &str) -> Result<Self, SettingsError> {
+        let settings: Settings = serde_json::from_str(raw_settings)?;
+        settings.storage.path_template()?;
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::inbox::storage::Project;
+    use crate::nodes::inbox::Position;
+
+    #[test]
+    fn parses_a_complete_gitlab_configuration() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{
+                "storage": {
+                    "backend": "gitlab",
+                    "base_url": "https://git.example.com",
+                    "project": "paytonrules/gtd",
+                    "file_path": "gtd/inbox.org",
+                    "branch": "main"
+                }
+            }"#,
+        )?;
+
+        assert_eq!(
+            StorageConfig::Gitlab(GitlabConfig {
+                base_url: "https://git.example.com".to_string(),
+                project: Project::Path("paytonrules/gtd".to_string()),
+                file_path: "gtd/inbox.org".to_string(),
+                branch: "main".to_string(),
+            }),
+            settings.storage
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parses_a_github_configuration() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{
+                "storage": {
+                    "backend": "github",
+                    "owner": "paytonrules",
+                    "repo": "gtd",
+                    "file_path": "inbox.org",
+                    "token": "personal-access-token"
+                }
+            }"#,
+        )?;
+
+        assert_eq!(
+            StorageConfig::Github(GithubConfig {
+                api_url: "https://api.github.com".to_string(),
+                owner: "paytonrules".to_string(),
+                repo: "gtd".to_string(),
+                file_path: "inbox.org".to_string(),
+                branch: "main".to_string(),
+                token: "personal-access-token".to_string(),
+            }),
+            settings.storage
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parses_a_file_configuration() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{"storage": {"backend": "file", "path": "/home/me/org/inbox.org"}}"#,
+        )?;
+
+        assert_eq!(
+            StorageConfig::File(FileConfig {
+                path: PathBuf::from("/home/me/org/inbox.org"),
+            }),
+            settings.storage
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn every_backend_has_an_inbox_file() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{"storage": {"backend": "file", "path": "/home/me/notes/Inbox.md"}}"#,
+        )?;
+
+        assert_eq!("/home/me/notes/Inbox.md", settings.storage.file_path());
+        Ok(())
+    }
+
+    #[test]
+    fn parses_a_git_repository_configuration() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{"storage": {
+                "backend": "git",
+                "repository": "/home/me/org",
+                "file_path": "gtd/inbox.org",
+                "remote": "origin"
+            }}"#,
+        )?;
+
+        assert_eq!(
+            StorageConfig::Git(GitRepoConfig {
+                repository: PathBuf::from("/home/me/org"),
+                file_path: "gtd/inbox.org".to_string(),
+                remote: Some("origin".to_string()),
+            }),
+            settings.storage
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn settings_with_an_unknown_backend_are_invalid() {
+        let settings = Settings::parse(r#"{"storage": {"backend": "dropbox"}}"#);
+
+        assert!(
+            matches!(settings, Err(SettingsError::Invalid(_))),
+            "expected invalid settings, got {:?}",
+            settings
+        );
+    }
+
+    #[test]
+    fn settings_without_a_storage_section_are_invalid() {
+        let settings = Settings::parse("{}");
+
+        assert!(
+            matches!(settings, Err(SettingsError::Invalid(_))),
+            "expected invalid settings, got {:?}",
+            settings
+        );
+    }
+
+    #[test]
+    fn missing_settings_file_reports_where_it_looked() {
+        let path = Path::new("/this/path/does/not/exist/settings.json");
+
+        let settings = Settings::load(path);
+
+        match settings {
+            Err(SettingsError::Missing(missing_path)) => assert_eq!(path, missing_path),
+            _ => panic!("expected missing settings, got {:?}", settings),
+        }
+    }
+
+    #[test]
+    fn batching_is_off_unless_configured() -> Result<(), SettingsError> {
+        let settings =
+            Settings::parse(r#"{"storage": {"backend": "file", "path": "/home/me/inbox.org"}}"#)?;
+
+        assert_eq!(None, settings.batching);
+        Ok(())
+    }
+
+    #[test]
+    fn parses_the_batching_window() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{
+                "storage": {"backend": "file", "path": "/home/me/inbox.org"},
+                "batching": {"window_seconds": 30}
+            }"#,
+        )?;
+
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            settings.batching.map(|batching| batching.window())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reminders_go_under_inbox_unless_configured() -> Result<(), SettingsError> {
+        let settings =
+            Settings::parse(r#"{"storage": {"backend": "file", "path": "/home/me/inbox.org"}}"#)?;
+
+        assert_eq!(Target::default(), settings.target);
+        Ok(())
+    }
+
+    #[test]
+    fn parses_the_capture_target() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{
+                "storage": {"backend": "file", "path": "/home/me/gtd.org"},
+                "target": {"outline": "Work/Inbox", "level": 3, "position": "prepend"}
+            }"#,
+        )?;
+
+        assert_eq!(
+            Target::new("Work/Inbox", Some(3), Position::Prepend).unwrap(),
+            settings.target
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parses_what_to_record_with_each_reminder() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{
+                "storage": {"backend": "file", "path": "/home/me/gtd.org"},
+                "metadata": {"timestamp": true, "properties": true}
+            }"#,
+        )?;
+
+        assert_eq!(
+            Metadata {
+                timestamp: true,
+                properties: true,
+                captured_from: None,
+            },
+            settings.metadata
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parses_the_todo_keywords() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{
+                "storage": {"backend": "file", "path": "/home/me/gtd.org"},
+                "todo_keywords": ["NEXT", "WAITING", "|", "DONE", "CANCELLED"]
+            }"#,
+        )?;
+
+        assert_eq!(
+            TodoKeywords::new(&["NEXT", "WAITING", "|", "DONE", "CANCELLED"]),
+            settings.todo_keywords
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parses_the_archive_location() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{
+                "storage": {"backend": "file", "path": "/home/me/gtd.org"},
+                "archive": "archive.org::* From the inbox"
+            }"#,
+        )?;
+
+        assert_eq!(
+            ArchiveLocation::new("archive.org::From the inbox").unwrap(),
+            settings.archive
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parses_capture_templates() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{
+                "storage": {"backend": "file", "path": "/home/me/gtd.org"},
+                "templates": [{"name": "Todo", "template": "** TODO %?\n   %U"}]
+            }"#,
+        )?;
+
+        assert_eq!(
+            vec![CaptureTemplate {
+                name: "Todo".to_string(),
+                template: "** TODO %?\n   %U".to_string(),
+            }],
+            settings.templates
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn a_target_level_above_its_outline_is_invalid() {
+        let settings = Settings::parse(
+            r#"{
+                "storage": {"backend": "file", "path": "/home/me/gtd.org"},
+                "target": {"outline": "Work/Inbox", "level": 1}
+            }"#,
+        );
+
+        assert!(matches!(settings, Err(SettingsError::Invalid(_))));
+    }
+
+    #[test]
+    fn templated_paths_are_resolved_into_the_same_storage() -> Result<(), SettingsError> {
+        let settings = Settings::parse(
+            r#"{"storage": {"backend": "file", "path": "/home/me/journal/{yyyy}-{mm}-{dd}.org"}}"#,
+        )?;
+
+        assert!(!settings.storage.path_template()?.is_fixed());
+        assert_eq!(
+            "/home/me/journal/2026-10-18.org",
+            settings
+                .storage
+                .with_file_path("/home/me/journal/2026-10-18.org")
+                .file_path()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_path_placeholders_are_invalid() {
+        let settings = Settings::parse(
+            r#"{"storage": {"backend": "file", "path": "/home/me/journal/{date}.org"}}"#,
+        );
+
+        assert!(matches!(settings, Err(SettingsError::InvalidPath(_))));
+    }
+}